@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File extensions which are registered as components.
+const COMPONENT_EXTENSIONS: [&str; 2] = ["html", "htm"];
+
+/// Find every component in a directory (and its subdirectories), keyed by the element name which renders it.
+/// `_components/post-card.html` is rendered by `<post-card>`, and `_components/cards/author-card.html` by `<author-card>`.
+pub fn load_components_dir(dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    let mut components: HashMap<String, PathBuf> = HashMap::new();
+
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>())
+        .map_err(|err| format!("{}: {}", dir.display(), err))?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+
+        if path.is_dir() {
+            for (name, component) in load_components_dir(&path)? {
+                if components.contains_key(&name) {
+                    warn!("{}: <{}> was already registered by another file", component.display(), name);
+                    continue;
+                }
+                components.insert(name, component);
+            }
+            continue;
+        }
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if !COMPONENT_EXTENSIONS.contains(&extension) {
+            continue;
+        }
+
+        // element names are lowercased when parsed, so the file's name is too.
+        let name = name[..name.len() - extension.len() - 1].to_lowercase();
+        debug!("registered component {} as <{}>", path.display(), name);
+        if components.contains_key(&name) {
+            warn!("{}: <{}> was already registered by another file", path.display(), name);
+            continue;
+        }
+        components.insert(name, path);
+    }
+
+    Ok(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_components_dir() {
+        let dir = TempDir::new("components");
+        dir.write("post-card.html", "");
+        dir.write("cards/Author-Card.htm", "");
+        dir.write("notes.md", "");
+
+        let components = crate::components::load_components_dir(dir.path()).unwrap();
+
+        assert_eq!(components.len(), 2);
+        assert_eq!(components["post-card"], dir.path().join("post-card.html"));
+        assert_eq!(components["author-card"], dir.path().join("cards/Author-Card.htm"));
+    }
+}
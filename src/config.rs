@@ -0,0 +1,174 @@
+use cargo_toml::Value as TOMLValue;
+use serde::Deserialize;
+use serde_json::Value as JSONValue;
+use serde_yaml::Value as YAMLValue;
+use std::path::{Path, PathBuf};
+
+/// Names checked for a configuration file at the root of a site, in order.
+pub const CONFIG_FILES: [&str; 3] = ["docgen.yaml", "docgen.yml", "docgen.toml"];
+
+/// Directory the site is written to when the configuration doesn't set `output`.
+pub const DEFAULT_OUTPUT: &str = "_site";
+
+/// Data directory used when the configuration doesn't set `data`.
+pub const DEFAULT_DATA: &str = "_data";
+
+/// Components directory used when the configuration doesn't set `components`.
+pub const DEFAULT_COMPONENTS: &str = "_components";
+
+/// Project-level settings, read from `docgen.yaml` or `docgen.toml` at the root of a site.
+/// Paths are relative to the directory containing the configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory containing the site's pages.
+    pub source: PathBuf,
+
+    /// Directory the rendered site is written to.
+    pub output: PathBuf,
+
+    /// Globs (relative to the source directory) of files to build. Everything is built if empty.
+    pub include: Vec<String>,
+
+    /// Globs (relative to the source directory) of files to skip.
+    pub exclude: Vec<String>,
+
+    /// Layout used by pages which don't set a `layout` of their own.
+    pub layout: Option<String>,
+
+    /// The url the site is published at, exposed to templates as `site.base_url`.
+    pub base_url: Option<String>,
+
+    /// Pattern used to place dated pages which don't set a `permalink`.
+    pub permalink: String,
+
+    /// Data directory, relative to the source directory.
+    pub data: PathBuf,
+
+    /// Components directory, relative to the source directory.
+    pub components: PathBuf,
+
+    /// Arbitrary variables, merged into the `site` object of every page.
+    pub site: serde_json::Map<String, JSONValue>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            source: PathBuf::from("."),
+            output: PathBuf::from(DEFAULT_OUTPUT),
+            include: vec![],
+            exclude: vec![],
+            layout: None,
+            base_url: None,
+            permalink: crate::site::DEFAULT_PERMALINK.to_string(),
+            data: PathBuf::from(DEFAULT_DATA),
+            components: PathBuf::from(DEFAULT_COMPONENTS),
+            site: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Parse a configuration file's contents. TOML is used for `.toml` files, YAML for anything else.
+    pub fn parse(contents: &str, toml: bool) -> Result<Config, String> {
+        // an empty file is the default configuration.
+        if contents.trim().is_empty() {
+            return Ok(Config::default());
+        }
+
+        // both formats go through json, so toml datetimes become strings like they do in front matter.
+        let value = if toml {
+            let value = contents.parse::<TOMLValue>().map_err(|err| format!("{}", err))?;
+            crate::frontmatter::toml_to_json(&value)
+        } else {
+            let value: YAMLValue = serde_yaml::from_str(contents).map_err(|err| format!("{}", err))?;
+            serde_json::to_value(value).map_err(|err| format!("{}", err))?
+        };
+
+        serde_json::from_value(value).map_err(|err| format!("{}", err))
+    }
+
+    /// Load a configuration file.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let toml = path.extension().and_then(|ext| ext.to_str()) == Some("toml");
+
+        Config::parse(&contents, toml).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Find and load the configuration file at the root of a site, falling back to the defaults.
+    pub fn find(root: &Path) -> Result<Config, String> {
+        for name in CONFIG_FILES.iter() {
+            let path = root.join(name);
+            if path.is_file() {
+                debug!("using configuration file {}", path.display());
+                return Config::load(&path);
+            }
+        }
+
+        Ok(Config::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_yaml_config() {
+        let config = Config::parse(
+            r###"
+source: src
+output: public
+exclude:
+  - drafts/**
+layout: _layouts/base.html
+base_url: https://richinfante.com
+site:
+  title: Rich Infante
+"###,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(config.source, PathBuf::from("src"));
+        assert_eq!(config.output, PathBuf::from("public"));
+        assert_eq!(config.exclude, vec!["drafts/**".to_string()]);
+        assert!(config.include.is_empty());
+        assert_eq!(config.layout, Some("_layouts/base.html".to_string()));
+        assert_eq!(config.base_url, Some("https://richinfante.com".to_string()));
+        assert_eq!(config.site["title"], "Rich Infante");
+        assert_eq!(config.permalink, crate::site::DEFAULT_PERMALINK);
+    }
+
+    #[test]
+    fn test_parse_toml_config() {
+        let config = Config::parse(
+            r###"
+output = "public"
+include = ["**/*.md"]
+
+[site]
+title = "Rich Infante"
+updated = 2019-03-01
+"###,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(config.source, PathBuf::from("."));
+        assert_eq!(config.output, PathBuf::from("public"));
+        assert_eq!(config.include, vec!["**/*.md".to_string()]);
+        assert_eq!(config.site["title"], "Rich Infante");
+        assert_eq!(config.site["updated"], "2019-03-01");
+    }
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = Config::parse("", false).unwrap();
+        assert_eq!(config.output, PathBuf::from("_site"));
+    }
+}
@@ -0,0 +1,158 @@
+use cargo_toml::Value as TOMLValue;
+use serde_json::Value as JSONValue;
+use serde_yaml::Value as YAMLValue;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A value loaded from the data directory.
+/// Files keep their original format, so they convert to json the same way front matter does.
+pub enum DataValue {
+    JSON(JSONValue),
+    YAML(YAMLValue),
+    TOML(TOMLValue),
+    /// A directory of data files, keyed by file name without the extension.
+    Directory(BTreeMap<String, DataValue>),
+}
+
+impl DataValue {
+    /// Convert the value to json, using the conversion of the file's format.
+    pub fn to_json(&self) -> JSONValue {
+        match self {
+            DataValue::JSON(value) => value.clone(),
+            DataValue::YAML(value) => crate::frontmatter::yaml_to_json(value),
+            DataValue::TOML(value) => crate::frontmatter::toml_to_json(value),
+            DataValue::Directory(mapping) => JSONValue::Object(
+                mapping
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_json()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Load a single data file, based on its extension.
+/// Returns `Ok(None)` for files which aren't data files.
+pub fn load_data_file(path: &Path) -> Result<Option<DataValue>, String> {
+    let extension = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => extension,
+        None => return Ok(None),
+    };
+
+    if extension != "json" && extension != "yaml" && extension != "yml" && extension != "toml" {
+        return Ok(None);
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let value = match extension {
+        "json" => serde_json::from_str(&contents).map(DataValue::JSON).map_err(|err| format!("{}", err)),
+        "toml" => contents.parse::<TOMLValue>().map(DataValue::TOML).map_err(|err| format!("{}", err)),
+        _ => serde_yaml::from_str(&contents).map(DataValue::YAML).map_err(|err| format!("{}", err)),
+    };
+
+    value
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Load every data file in a directory (and its subdirectories).
+/// `_data/navigation.yaml` is available as `data.navigation`, `_data/authors/rich.json` as `data.authors.rich`.
+pub fn load_data_dir(dir: &Path) -> Result<DataValue, String> {
+    let mut values: BTreeMap<String, DataValue> = BTreeMap::new();
+
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>())
+        .map_err(|err| format!("{}: {}", dir.display(), err))?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = if path.is_dir() {
+            path.file_name()
+        } else {
+            path.file_stem()
+        };
+        let name = match name.and_then(|name| name.to_str()) {
+            Some(name) if !name.starts_with('.') => name.to_string(),
+            _ => continue,
+        };
+
+        let value = if path.is_dir() {
+            Some(load_data_dir(&path)?)
+        } else {
+            load_data_file(&path)?
+        };
+
+        if let Some(value) = value {
+            debug!("loaded data file {} as data.{}", path.display(), name);
+            if values.insert(name.clone(), value).is_some() {
+                warn!("{}: data.{} was already loaded from another file", path.display(), name);
+            }
+        }
+    }
+
+    Ok(DataValue::Directory(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::DataValue;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_data_dir() {
+        let dir = TempDir::new("data");
+        dir.write("navigation.yaml", "- title: Home\n  url: /\n");
+        dir.write("settings.toml", "title = \"Docgen\"\n");
+        dir.write("authors/rich.json", "{ \"name\": \"Rich\" }");
+        dir.write("notes.txt", "not data");
+
+        let data = crate::data::load_data_dir(dir.path()).unwrap();
+        assert_eq!(
+            data.to_json(),
+            serde_json::json!({
+                "authors": { "rich": { "name": "Rich" } },
+                "navigation": [{ "title": "Home", "url": "/" }],
+                "settings": { "title": "Docgen" },
+            })
+        );
+
+        let values = match data {
+            DataValue::Directory(values) => values,
+            _ => panic!("expected a directory"),
+        };
+
+        assert_eq!(
+            values.keys().collect::<Vec<&String>>(),
+            vec!["authors", "navigation", "settings"]
+        );
+
+        match &values["navigation"] {
+            DataValue::YAML(serde_yaml::Value::Sequence(items)) => assert_eq!(items.len(), 1),
+            _ => panic!("expected a yaml sequence"),
+        }
+
+        match &values["settings"] {
+            DataValue::TOML(value) => assert_eq!(value["title"].as_str(), Some("Docgen")),
+            _ => panic!("expected a toml table"),
+        }
+
+        match &values["authors"] {
+            DataValue::Directory(authors) => match &authors["rich"] {
+                DataValue::JSON(value) => assert_eq!(value["name"], "Rich"),
+                _ => panic!("expected json"),
+            },
+            _ => panic!("expected a directory"),
+        }
+    }
+
+    #[test]
+    fn test_load_invalid_data_file() {
+        let dir = TempDir::new("invalid_data");
+        let path = dir.write("invalid.json", "{ not json");
+
+        assert!(crate::data::load_data_file(&path).is_err());
+    }
+}
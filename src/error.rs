@@ -0,0 +1,185 @@
+use crate::source::SourceLocation;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What went wrong while rendering.
+#[derive(Debug)]
+pub enum ErrorCause {
+    /// A file couldn't be read (or written), such as a missing layout or partial.
+    Io(PathBuf, std::io::Error),
+
+    /// A javascript expression or script threw an exception.
+    Script(String),
+
+    /// The front matter of a page couldn't be parsed.
+    FrontMatter(String),
+
+    /// The template itself is invalid, such as an unsupported `x-for` notation.
+    Template(String),
+}
+
+/// An error encountered while rendering a template, with the location it happened at where known.
+#[derive(Debug)]
+pub struct DocgenError {
+    /// The file being rendered.
+    pub file: Option<PathBuf>,
+
+    /// The line in that file, starting at 1.
+    pub line: Option<usize>,
+
+    /// The column in that line, starting at 1.
+    pub column: Option<usize>,
+
+    /// The contents of the line, shown in a code frame beneath the error.
+    pub source: Option<String>,
+
+    pub cause: ErrorCause,
+}
+
+impl DocgenError {
+    pub fn new(cause: ErrorCause) -> DocgenError {
+        DocgenError {
+            file: None,
+            line: None,
+            column: None,
+            source: None,
+            cause,
+        }
+    }
+
+    pub fn io(path: &Path, err: std::io::Error) -> DocgenError {
+        DocgenError::new(ErrorCause::Io(path.to_path_buf(), err))
+    }
+
+    pub fn script(message: String) -> DocgenError {
+        DocgenError::new(ErrorCause::Script(message))
+    }
+
+    pub fn front_matter<E: fmt::Display>(err: E) -> DocgenError {
+        DocgenError::new(ErrorCause::FrontMatter(format!("{}", err)))
+    }
+
+    pub fn template(message: String) -> DocgenError {
+        DocgenError::new(ErrorCause::Template(message))
+    }
+
+    /// Attach the location of the expression which caused the error, if it isn't already known.
+    pub fn at(mut self, location: Option<&SourceLocation>) -> DocgenError {
+        if let (None, Some(location)) = (self.line, location) {
+            self.file = self.file.or_else(|| location.file.clone());
+            self.line = Some(location.line);
+            self.column = location.column;
+            self.source = Some(location.text.clone());
+        }
+
+        self
+    }
+
+    /// Attach the file being rendered, unless the error already happened in another (included) file.
+    pub fn in_file(mut self, file: Option<&Path>) -> DocgenError {
+        if self.file.is_none() {
+            self.file = file.map(Path::to_path_buf);
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCause::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ErrorCause::Script(message) => write!(f, "{}", message),
+            ErrorCause::FrontMatter(message) => write!(f, "invalid front matter: {}", message),
+            ErrorCause::Template(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Formats as `file:line:column: cause`, leaving out whichever parts of the location aren't known,
+/// followed by a code frame pointing at the line when it's known:
+///
+/// ```text
+/// index.html:12:9: Error: title is not defined
+///    |
+/// 12 |     <h1>{{ title }}</h1>
+///    |         ^
+/// ```
+impl fmt::Display for DocgenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+                if let Some(column) = self.column {
+                    write!(f, "{}:", column)?;
+                }
+            }
+            write!(f, " ")?;
+        }
+
+        write!(f, "{}", self.cause)?;
+
+        if let (Some(line), Some(source)) = (self.line, &self.source) {
+            let number = format!("{}", line);
+            let gutter = " ".repeat(number.len());
+            write!(f, "\n{} |\n{} | {}", gutter, number, source)?;
+
+            if let Some(column) = self.column {
+                // keep tabs, so the marker lines up with the source.
+                let indent: String = source
+                    .chars()
+                    .take(column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(f, "\n{} | {}^", gutter, indent)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for DocgenError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::DocgenError;
+    use crate::source::SourceLocation;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_display_location() {
+        let mut err = DocgenError::script("ReferenceError: title is not defined".to_string())
+            .in_file(Some(Path::new("posts/hello.md")));
+        assert_eq!(format!("{}", err), "posts/hello.md: ReferenceError: title is not defined");
+
+        err.line = Some(12);
+        err.column = Some(5);
+        assert_eq!(format!("{}", err), "posts/hello.md:12:5: ReferenceError: title is not defined");
+    }
+
+    #[test]
+    fn test_display_code_frame() {
+        let location = SourceLocation {
+            file: Some(PathBuf::from("index.html")),
+            line: 12,
+            column: Some(9),
+            text: "    <h1>{{ title }}</h1>".to_string(),
+        };
+        let err = DocgenError::script("Error: title is not defined".to_string()).at(Some(&location));
+
+        assert_eq!(
+            format!("{}", err),
+            "index.html:12:9: Error: title is not defined\n   |\n12 |     <h1>{{ title }}</h1>\n   |         ^"
+        );
+    }
+
+    #[test]
+    fn test_in_file_keeps_included_file() {
+        let err = DocgenError::template("unsupported for-loop notation: x".to_string())
+            .in_file(Some(Path::new("_includes/footer.html")))
+            .in_file(Some(Path::new("index.html")));
+        assert_eq!(err.file, Some(Path::new("_includes/footer.html").to_path_buf()));
+    }
+}
@@ -31,6 +31,7 @@ use serde_yaml::Value as YAMLValue;
 pub enum MatterType {
     JSON,
     YAML,
+    TOML,
     NotFound,
 }
 
@@ -75,21 +76,17 @@ impl EasyToJSVal for TOMLValue {
             TOMLValue::Table(mapping) => {
                 let obj = mozjs::jsapi::JS_NewObject(cx, &SIMPLE_GLOBAL_CLASS);
                 for (string, v) in mapping.iter() {
-                    let page_name = std::ffi::CString::new(string.as_str()).unwrap();
-                    let page_ptr = page_name.as_ptr() as *const i8;
-                    rooted!(in(cx) let object = mozjs::jsval::ObjectValue(obj).to_object());
-                    rooted!(in(cx) let value = v.convert_to_jsval(cx));
-                    mozjs::rust::wrappers::JS_SetProperty(
-                        cx,
-                        object.handle(),
-                        page_ptr,
-                        value.handle(),
-                    );
+                    set_property(cx, obj, string, v);
                 }
 
                 mozjs::jsval::ObjectValue(obj)
             }
-            _ => unimplemented!(),
+            TOMLValue::Datetime(datetime) => {
+                // datetimes become RFC 3339 strings, which `new Date(...)` understands.
+                rooted!(in(cx) let mut val = UndefinedValue());
+                datetime.to_string().to_jsval(cx, val.handle_mut());
+                val.get()
+            }
         }
     }
 }
@@ -111,14 +108,12 @@ impl EasyToJSVal for JSONValue {
             }
             JSONValue::Number(number) => {
                 rooted!(in(cx) let mut val = UndefinedValue());
-                if number.is_f64() {
-                    number.as_f64().unwrap().to_jsval(cx, val.handle_mut());
-                } else if number.is_u64() {
-                    number.as_u64().unwrap().to_jsval(cx, val.handle_mut());
-                } else if number.is_i64() {
-                    number.as_i64().unwrap().to_jsval(cx, val.handle_mut());
+                if let Some(number) = number.as_i64() {
+                    number.to_jsval(cx, val.handle_mut());
+                } else if let Some(number) = number.as_u64() {
+                    number.to_jsval(cx, val.handle_mut());
                 } else {
-                    panic!("unknown number type.");
+                    number.as_f64().unwrap_or(std::f64::NAN).to_jsval(cx, val.handle_mut());
                 }
 
                 val.get()
@@ -136,21 +131,11 @@ impl EasyToJSVal for JSONValue {
             JSONValue::Object(mapping) => {
                 let obj = mozjs::jsapi::JS_NewObject(cx, &SIMPLE_GLOBAL_CLASS);
                 for (string, v) in mapping.iter() {
-                    let page_name = std::ffi::CString::new(string.as_str()).unwrap();
-                    let page_ptr = page_name.as_ptr() as *const i8;
-                    rooted!(in(cx) let object = mozjs::jsval::ObjectValue(obj).to_object());
-                    rooted!(in(cx) let value = v.convert_to_jsval(cx));
-                    mozjs::rust::wrappers::JS_SetProperty(
-                        cx,
-                        object.handle(),
-                        page_ptr,
-                        value.handle(),
-                    );
+                    set_property(cx, obj, string, v);
                 }
 
                 mozjs::jsval::ObjectValue(obj)
             }
-            _ => unimplemented!(),
         }
     }
 }
@@ -172,14 +157,12 @@ impl EasyToJSVal for YAMLValue {
             }
             YAMLValue::Number(number) => {
                 rooted!(in(cx) let mut val = UndefinedValue());
-                if number.is_f64() {
-                    number.as_f64().unwrap().to_jsval(cx, val.handle_mut());
-                } else if number.is_u64() {
-                    number.as_u64().unwrap().to_jsval(cx, val.handle_mut());
-                } else if number.is_i64() {
-                    number.as_i64().unwrap().to_jsval(cx, val.handle_mut());
+                if let Some(number) = number.as_i64() {
+                    number.to_jsval(cx, val.handle_mut());
+                } else if let Some(number) = number.as_u64() {
+                    number.to_jsval(cx, val.handle_mut());
                 } else {
-                    panic!("unknown number type.");
+                    number.as_f64().unwrap_or(std::f64::NAN).to_jsval(cx, val.handle_mut());
                 }
 
                 val.get()
@@ -197,21 +180,7 @@ impl EasyToJSVal for YAMLValue {
             YAMLValue::Mapping(mapping) => {
                 let obj = mozjs::jsapi::JS_NewObject(cx, &SIMPLE_GLOBAL_CLASS);
                 for (k, v) in mapping.iter() {
-                    match k {
-                        YAMLValue::String(string) => {
-                            let page_name = std::ffi::CString::new(string.as_str()).unwrap();
-                            let page_ptr = page_name.as_ptr() as *const i8;
-                            rooted!(in(cx) let object = mozjs::jsval::ObjectValue(obj).to_object());
-                            rooted!(in(cx) let value = v.convert_to_jsval(cx));
-                            mozjs::rust::wrappers::JS_SetProperty(
-                                cx,
-                                object.handle(),
-                                page_ptr,
-                                value.handle(),
-                            );
-                        }
-                        _ => unimplemented!(),
-                    }
+                    set_property(cx, obj, &yaml_key(k), v);
                 }
 
                 mozjs::jsval::ObjectValue(obj)
@@ -220,22 +189,56 @@ impl EasyToJSVal for YAMLValue {
     }
 }
 
+/// Set a property of an object converted from front matter. Names containing a nul byte can't be passed to
+/// spidermonkey, so those properties are skipped.
+pub(crate) unsafe fn set_property<T: EasyToJSVal>(cx: *mut JSContext, obj: *mut mozjs::jsapi::JSObject, name: &str, value: &T) {
+    let name = match std::ffi::CString::new(name) {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+
+    rooted!(in(cx) let object = obj);
+    rooted!(in(cx) let value = value.convert_to_jsval(cx));
+    mozjs::rust::wrappers::JS_SetProperty(cx, object.handle(), name.as_ptr() as *const i8, value.handle());
+}
+
+/// The property name for a YAML mapping key. Javascript only has string keys, so other scalars (`1: one`,
+/// `true: yes`) use their YAML representation, as do the rare sequence or mapping keys.
+pub fn yaml_key(key: &YAMLValue) -> String {
+    match key {
+        YAMLValue::String(string) => string.clone(),
+        YAMLValue::Number(number) => number.to_string(),
+        YAMLValue::Bool(boolean) => boolean.to_string(),
+        YAMLValue::Null => "null".to_string(),
+        _ => serde_yaml::to_string(key)
+            .map(|yaml| yaml.trim_start_matches("---").trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
 /// Front matter is defined by the block at the top of a document, separated by triple dashes "---"
+/// (or triple pluses "+++" for TOML)
 pub fn extract_frontmatter(document: &str) -> (Option<&str>, &str) {
     let mut trimmed = document.trim();
 
-    if !trimmed.starts_with("---\n") {
+    let delimiter = if trimmed.starts_with("---\n") {
+        '-'
+    } else if trimmed.starts_with("+++\n") {
+        '+'
+    } else {
         return (None, document);
-    }
+    };
 
     trimmed = trimmed[3..].trim();
     let mut was_newline = false;
     let mut dash_counter = 0;
-    for (i, c) in trimmed.chars().enumerate() {
-        if was_newline && c == '-' {
+    for (i, c) in trimmed.char_indices() {
+        if was_newline && c == delimiter {
             dash_counter += 1;
         } else if c == '\n' {
+            // only count dashes at the start of a line, so yaml lists ("- item") don't close the block.
             was_newline = true;
+            dash_counter = 0;
         } else {
             was_newline = false;
         }
@@ -260,13 +263,94 @@ pub fn infer_type(matter: &str) -> MatterType {
         } else {
             MatterType::YAML
         }
+    } else if trimmed.starts_with("+++\n") {
+        MatterType::TOML
     } else {
         MatterType::NotFound
     }
 }
 
+/// Convert a TOML value into JSON, turning datetimes into strings like the JS conversion does.
+pub fn toml_to_json(value: &TOMLValue) -> JSONValue {
+    match value {
+        TOMLValue::String(string) => JSONValue::String(string.clone()),
+        TOMLValue::Integer(number) => JSONValue::from(*number),
+        TOMLValue::Float(number) => JSONValue::from(*number),
+        TOMLValue::Boolean(boolean) => JSONValue::Bool(*boolean),
+        TOMLValue::Datetime(datetime) => JSONValue::String(datetime.to_string()),
+        TOMLValue::Array(vector) => JSONValue::Array(vector.iter().map(toml_to_json).collect()),
+        TOMLValue::Table(mapping) => JSONValue::Object(
+            mapping
+                .iter()
+                .map(|(key, value)| (key.clone(), toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a YAML value into JSON. Keys which aren't strings are converted with `yaml_key`.
+pub fn yaml_to_json(value: &YAMLValue) -> JSONValue {
+    match value {
+        YAMLValue::Null => JSONValue::Null,
+        YAMLValue::Bool(boolean) => JSONValue::Bool(*boolean),
+        YAMLValue::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                JSONValue::from(number)
+            } else if let Some(number) = number.as_u64() {
+                JSONValue::from(number)
+            } else {
+                // json has no NaN or infinities, so they become null.
+                JSONValue::from(number.as_f64().unwrap_or(std::f64::NAN))
+            }
+        }
+        YAMLValue::String(string) => JSONValue::String(string.clone()),
+        YAMLValue::Sequence(vector) => JSONValue::Array(vector.iter().map(yaml_to_json).collect()),
+        YAMLValue::Mapping(mapping) => JSONValue::Object(
+            mapping
+                .iter()
+                .map(|(key, value)| (yaml_key(key), yaml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Parse a document's front matter into JSON, so it can be inspected without a javascript engine.
+pub fn parse_to_json(document: &str) -> Option<JSONValue> {
+    match infer_type(document) {
+        MatterType::YAML => {
+            let matter = extract_frontmatter(document).0?;
+            let value: YAMLValue = serde_yaml::from_str(matter).ok()?;
+            Some(yaml_to_json(&value))
+        }
+        MatterType::JSON => {
+            let matter = extract_frontmatter(document).0?;
+            serde_json::from_str(matter).ok()
+        }
+        MatterType::TOML => {
+            let matter = extract_frontmatter(document).0?;
+            let value: TOMLValue = matter.parse().ok()?;
+            Some(toml_to_json(&value))
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_yaml_keys() {
+        let value: serde_yaml::Value = serde_yaml::from_str("title: a\n1: b\ntrue: c\n~: d\n").unwrap();
+        let keys = match value {
+            serde_yaml::Value::Mapping(mapping) => mapping
+                .iter()
+                .map(|(key, _)| crate::frontmatter::yaml_key(key))
+                .collect::<Vec<String>>(),
+            _ => vec![],
+        };
+
+        assert_eq!(keys, vec!["title", "1", "true", "null"]);
+    }
+
     #[test]
     fn test_infer_json() {
         let doc = r###"
@@ -282,6 +366,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_json_matter() {
+        let doc = r###"---
+{
+    "title": "JSON Example",
+    "tags": ["a", "b", "c"]
+}
+---
+# Hello"###;
+
+        let (matter, contents) = crate::frontmatter::extract_frontmatter(doc);
+        assert_eq!(contents, "\n# Hello");
+
+        let value: serde_json::Value = serde_json::from_str(matter.unwrap()).unwrap();
+        assert_eq!(value["title"], "JSON Example");
+        assert_eq!(value["tags"][2], "c");
+    }
+
+    #[test]
+    fn test_parse_json_matter() {
+        let doc = r###"---
+{ "title": "JSON Example", "permalink": "/json-example" }
+---
+body"###;
+
+        assert_eq!(
+            crate::frontmatter::parse_to_json(doc),
+            Some(serde_json::json!({
+                "title": "JSON Example",
+                "permalink": "/json-example"
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml_matter() {
+        let doc = r###"---
+title: YAML Example
+count: 3
+---
+body"###;
+
+        assert_eq!(
+            crate::frontmatter::parse_to_json(doc),
+            Some(serde_json::json!({ "title": "YAML Example", "count": 3 }))
+        );
+    }
+
     #[test]
     fn test_infer_yaml() {
         let doc = r###"---
@@ -294,6 +426,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_infer_toml() {
+        let doc = r###"+++
+        title = "TOML Front Matter"
+        +++"###;
+
+        assert_eq!(
+            crate::frontmatter::infer_type(&doc),
+            crate::frontmatter::MatterType::TOML
+        );
+    }
+
+    #[test]
+    fn test_extract_toml_matter() {
+        let doc = r###"+++
+title = "TOML Front Matter"
+tags = ["a", "b"]
++++
+body"###;
+
+        assert_eq!(
+            crate::frontmatter::extract_frontmatter(doc),
+            (
+                Some(
+                    r###"title = "TOML Front Matter"
+tags = ["a", "b"]"###
+                ),
+                "\nbody"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_toml_matter() {
+        let doc = r###"+++
+title = "Hugo Post"
+date = 2019-03-01T08:00:00-04:00
+draft = false
++++
+body"###;
+
+        assert_eq!(
+            crate::frontmatter::parse_to_json(doc),
+            Some(serde_json::json!({
+                "title": "Hugo Post",
+                "date": "2019-03-01T08:00:00-04:00",
+                "draft": false
+            }))
+        );
+    }
+
     #[test]
     fn test_infer_none() {
         let doc = r###"some article begins right here. nothing to see."###;
@@ -304,6 +487,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_matter_with_list() {
+        let doc = r###"---
+title: Lists
+categories:
+- notes
+- javascript
+- rust
+---
+body"###;
+
+        assert_eq!(
+            crate::frontmatter::extract_frontmatter(doc),
+            (
+                Some(
+                    r###"title: Lists
+categories:
+- notes
+- javascript
+- rust"###
+                ),
+                "\nbody"
+            )
+        );
+    }
+
     #[test]
     fn test_extract_matter() {
         let doc = r###"---
@@ -29,6 +29,10 @@ pub use mozjs::rust::{JSEngine, ParentRuntime, Runtime, SIMPLE_GLOBAL_CLASS};
 #[macro_use]
 extern crate log;
 
+#[cfg(test)]
+#[macro_use]
+extern crate lazy_static;
+
 use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::cell::{Ref, RefMut};
@@ -42,8 +46,21 @@ use std::ptr;
 use std::rc::Rc;
 use std::rc::Weak;
 
+pub use error::{DocgenError, ErrorCause};
+use source::SourceLocation;
+
+pub mod components;
+pub mod config;
+pub mod data;
+pub mod error;
 pub mod frontmatter;
 pub mod render;
+pub mod site;
+pub mod source;
+pub mod vars;
+
+#[cfg(test)]
+mod testing;
 
 use frontmatter::EasyToJSVal;
 
@@ -144,7 +161,19 @@ unsafe extern "C" fn read_file_to_string(
     let js = mozjs::rust::ToString(context, arg);
     let script_src = mozjs::conversions::jsstr_to_string(context, js);
 
-    let loaded_script_file = std::fs::read_to_string(std::path::Path::new(&script_src)).unwrap();
+    let loaded_script_file = match std::fs::read_to_string(std::path::Path::new(&script_src)) {
+        Ok(loaded_script_file) => loaded_script_file,
+        Err(err) => {
+            // throw, so the error surfaces as an exception in the calling script.
+            let message = std::ffi::CString::new(format!("fs.readFileSync(): {}: {}", script_src, err)).unwrap();
+            mozjs::jsapi::JS_ReportErrorASCII(
+                context,
+                b"%s\0".as_ptr() as *const libc::c_char,
+                message.as_ptr(),
+            );
+            return false;
+        }
+    };
 
     rooted!(in(context) let mut val = UndefinedValue());
     loaded_script_file.to_jsval(context, val.handle_mut());
@@ -156,6 +185,10 @@ unsafe extern "C" fn read_file_to_string(
     return true;
 }
 
+/// Stands in for values which can't be stringified without the engine. Templates use `try_stringify_jsvalue`,
+/// so this only ends up in messages and logs.
+const STRINGIFY_ERROR: &str = "(error: stringify_jsvalue unimplemented type!)";
+
 /// Stringify a jsval into a rust string for injection into the template
 unsafe fn stringify_jsvalue(cx: *mut JSContext, rval: &JSVal) -> String {
     if rval.is_number() {
@@ -178,9 +211,361 @@ unsafe fn stringify_jsvalue(cx: *mut JSContext, rval: &JSVal) -> String {
         }
     }
 
-    // TODO: json stringify here.
+    return STRINGIFY_ERROR.to_string();
+}
+
+/// Renders objects and symbols as text, much like Vue does: arrays and plain objects as json,
+/// dates as ISO 8601, and objects with their own `toString` through it. Functions can't be rendered.
+const STRINGIFY_SCRIPT: &str = r###"
+(function (value) {
+    if (typeof value === "function") {
+        throw new TypeError("can't render a function as text");
+    }
+
+    if (typeof value === "symbol") {
+        return value.toString();
+    }
+
+    if (value instanceof Date) {
+        return isNaN(value.getTime()) ? "Invalid Date" : value.toISOString();
+    }
+
+    if (value instanceof Map || value instanceof Set) {
+        return JSON.stringify(Array.from(value));
+    }
+
+    if (!Array.isArray(value) && typeof value.toString === "function" && value.toString !== Object.prototype.toString) {
+        return String(value);
+    }
+
+    return JSON.stringify(value);
+})(__docgen.value)
+"###;
+
+/// Render a `:class` binding the way Vue does: an object's truthy keys, an array's items, or a string.
+const CLASS_SCRIPT: &str = r###"
+(function classes(value) {
+    if (value === null || value === undefined || value === false) {
+        return "";
+    }
+
+    if (Array.isArray(value)) {
+        return value.map(classes).filter(function (name) { return name; }).join(" ");
+    }
+
+    if (typeof value === "object") {
+        return Object.keys(value).filter(function (name) { return value[name]; }).join(" ");
+    }
+
+    return String(value).trim();
+})(__docgen.value)
+"###;
+
+/// Render a `:style` binding the way Vue does: an object of properties (which may be camelCase), an array of them, or a string.
+const STYLE_SCRIPT: &str = r###"
+(function styles(value) {
+    if (value === null || value === undefined || value === false) {
+        return "";
+    }
+
+    if (Array.isArray(value)) {
+        return value.map(styles).filter(function (style) { return style; }).join("; ");
+    }
+
+    if (typeof value === "object") {
+        return Object.keys(value).filter(function (name) {
+            return value[name] !== null && value[name] !== undefined && value[name] !== false && value[name] !== "";
+        }).map(function (name) {
+            var property = name.indexOf("--") === 0 ? name : name.replace(/[A-Z]/g, function (c) { return "-" + c.toLowerCase(); });
+            return property + ": " + value[name];
+        }).join("; ");
+    }
+
+    return String(value).trim().replace(/;$/, "");
+})(__docgen.value)
+"###;
+
+/// Attributes which are present or absent depending on whether their binding is truthy.
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "checked",  // checkbox
+    "selected", // select box
+    "disabled", // input
+    "readonly", // input
+    "autoplay", // video
+    "controls", // video
+    "loop",     // video
+    "muted",    // video
+    "open",     // summary
+];
+
+/// Stringify a jsval for output into a template, including objects, arrays and symbols.
+unsafe fn try_stringify_jsvalue(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    rval: &JSVal,
+) -> Result<String, DocgenError> {
+    if !rval.is_object() && !rval.is_symbol() {
+        return Ok(stringify_jsvalue(cx, rval));
+    }
+
+    stringify_with_script(global, rt, cx, STRINGIFY_SCRIPT, rval)
+}
+
+/// Run one of the stringify scripts above on a jsval, returning the string it produces.
+unsafe fn stringify_with_script(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    script: &str,
+    rval: &JSVal,
+) -> Result<String, DocgenError> {
+    eval_with_value(global, rt, cx, rval, script).map(|string| stringify_jsvalue(cx, &string))
+}
+
+/// Creates the object docgen keeps its own state in, `__docgen`, on a global: `value` passes a jsval into a
+/// script, and `scopes` is the stack of loop scopes. It's a single binding which can't be enumerated or
+/// overwritten, so it stays out of the way of a page's variables.
+const HELPERS_SCRIPT: &str = r###"
+(function (helpers) {
+    Object.defineProperty(this, "__docgen", { value: helpers });
+    return helpers;
+}).call(this, { value: undefined, scopes: [] })
+"###;
+
+/// Get the `__docgen` object of a global, creating it the first time it's needed.
+unsafe fn docgen_helpers(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+) -> Result<JSVal, DocgenError> {
+    let helpers_name = std::ffi::CString::new("__docgen").unwrap();
+    rooted!(in(cx) let mut helpers = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, global.handle(), helpers_name.as_ptr() as *const i8, helpers.handle_mut());
+    if helpers.is_object() {
+        return Ok(helpers.get());
+    }
+
+    let rval = rt.evaluate_script(global.handle(), HELPERS_SCRIPT, "docgen_helpers", 1, helpers.handle_mut());
+    if rval.is_err() {
+        let exception = fmt_exception(&rt, cx);
+        mozjs::jsapi::JS_ClearPendingException(cx);
+        return Err(DocgenError::script(exception));
+    }
+
+    Ok(helpers.get())
+}
+
+/// Evaluate a script which reads a jsval from `__docgen.value`.
+unsafe fn eval_with_value(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    rval: &JSVal,
+    script: &str,
+) -> Result<JSVal, DocgenError> {
+    // pass the value to the script, and clear it afterwards so it can be collected.
+    rooted!(in(cx) let helpers = docgen_helpers(global, rt, cx)?.to_object());
+    let value_name = std::ffi::CString::new("value").unwrap();
+    let value_ptr = value_name.as_ptr() as *const i8;
+    rooted!(in(cx) let value = *rval);
+    mozjs::rust::wrappers::JS_SetProperty(cx, helpers.handle(), value_ptr, value.handle());
+    let result = eval(global, rt, cx, script);
+    rooted!(in(cx) let undefined = UndefinedValue());
+    mozjs::rust::wrappers::JS_SetProperty(cx, helpers.handle(), value_ptr, undefined.handle());
+
+    result
+}
+
+/// Collect the items of a loop into an array of entries, which are the arguments of each iteration's scope.
+/// Called with the loop's kind (`in` or `of`), whether it binds a single name (rather than a tuple such as
+/// `(value, key)`) and the value to iterate:
+/// - a number `n` counts from 1 to n.
+/// - iterables (arrays, strings, maps, sets) iterate their items, with either kind.
+/// - other objects iterate their values with `of`, and their keys with `in`.
+/// Entries are `[item, index]` for a single name, and `[value, key, index]` for a tuple.
+const ITERATE_SCRIPT: &str = r###"
+(function (kind, single, value) {
+    var entries;
+    if (typeof value === "number") {
+        entries = Array.from({ length: value }, function (_, index) { return [index + 1, index]; });
+    } else if (value !== null && value !== undefined && typeof value[Symbol.iterator] === "function") {
+        entries = Array.from(value, function (item, index) { return [item, index]; });
+    } else if (value !== null && typeof value === "object") {
+        entries = Object.keys(value).map(function (key) {
+            return [kind === "in" && single ? key : value[key], key];
+        });
+    } else {
+        throw new TypeError("value is not iterable");
+    }
+
+    return entries.map(function (entry, index) {
+        return single ? [entry[0], index] : [entry[0], entry[1], index];
+    });
+})
+"###;
+
+/// Whether there's a loop scope to evaluate expressions in.
+/// Loop variables live in a stack of scopes, `__docgen.scopes`, on the page's global. Each scope is a
+/// function which evaluates code in a closure over the loop's variables (and any outer loop's).
+unsafe fn in_loop_scope(global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>, cx: *mut JSContext) -> bool {
+    let helpers_name = std::ffi::CString::new("__docgen").unwrap();
+    rooted!(in(cx) let mut helpers = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, global.handle(), helpers_name.as_ptr() as *const i8, helpers.handle_mut());
+    if !helpers.is_object() {
+        return false;
+    }
+
+    let scopes_name = std::ffi::CString::new("scopes").unwrap();
+    rooted!(in(cx) let helpers = helpers.to_object());
+    rooted!(in(cx) let mut scopes = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, helpers.handle(), scopes_name.as_ptr() as *const i8, scopes.handle_mut());
+    if !scopes.is_object() {
+        return false;
+    }
+
+    let length_name = std::ffi::CString::new("length").unwrap();
+    rooted!(in(cx) let scopes = scopes.to_object());
+    rooted!(in(cx) let mut length = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, scopes.handle(), length_name.as_ptr() as *const i8, length.handle_mut());
+    boolify_jsvalue(cx, &length)
+}
+
+/// Creates the scope for one iteration of a loop, which is called with the loop's variables as `__docgen.value`.
+/// The scope is a generator, so code is evaluated in the same call each time: `var`s declared by a static script
+/// stay in scope for the rest of the iteration. Errors are passed back out, since a generator which throws is done.
+const SCOPE_SCRIPT: &str = r###"
+__docgen.scopes.push((function (scope) {
+    scope.next();
+    return function (expression) {
+        var result = scope.next(expression).value;
+        if (result.threw) {
+            throw result.value;
+        }
+        return result.value;
+    };
+})((function* (PARAMS) {
+    var __docgen_result;
+    while (true) {
+        var __docgen_expression = yield __docgen_result;
+        try {
+            __docgen_result = { value: eval(__docgen_expression) };
+        } catch (error) {
+            __docgen_result = { threw: true, value: error };
+        }
+    }
+}).apply(this, __docgen.value)))
+"###;
+
+/// Start a scope for one iteration of a loop, binding `params` (such as `item, i`) to the items of the `args` array.
+unsafe fn push_scope(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    params: &str,
+    args: &JSVal,
+) -> Result<(), DocgenError> {
+    // this is evaluated in the current scope, so the new one can see the variables of any outer loops.
+    eval_with_value(global, rt, cx, args, &SCOPE_SCRIPT.replace("PARAMS", params)).map(|_| ())
+}
+
+/// End the innermost loop scope.
+unsafe fn pop_scope(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+) -> Result<(), DocgenError> {
+    eval(global, rt, cx, "__docgen.scopes.pop()").map(|_| ())
+}
+
+/// Quote a string as a javascript string literal.
+fn js_string_literal(string: &str) -> String {
+    // json strings are javascript strings, except for these two line terminators.
+    serde_json::to_string(string)
+        .unwrap()
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+/// Evaluate a `:name="expression"` attribute binding.
+/// Returns the attribute to render, or `None` when it should be left out.
+unsafe fn bind_attribute(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    name: &str,
+    script: &str,
+    location: Option<&SourceLocation>,
+    options: &RenderOptions,
+) -> Result<Option<Attribute>, DocgenError> {
+    let final_name = &name[1..];
+
+    // in lenient mode, an attribute which fails to evaluate is left out.
+    let value: JSVal = match options.recover(eval_at(global, rt, cx, script, location).map(Some), None)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let text = if BOOLEAN_ATTRIBUTES.contains(&final_name) {
+        // only if it's a boolean, perform boolification of it for the conditional
+        if !boolify_jsvalue(cx, &value) {
+            return Ok(None);
+        }
+        String::new()
+    } else {
+        let text = match final_name {
+            "class" => stringify_with_script(global, rt, cx, CLASS_SCRIPT, &value),
+            "style" => stringify_with_script(global, rt, cx, STYLE_SCRIPT, &value),
+            _ => try_stringify_jsvalue(global, rt, cx, &value),
+        };
+        // in lenient mode, an attribute which fails to stringify is left out too.
+        let text = match options.recover(text.map_err(|err| err.at(location)).map(Some), None)? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+
+        // an empty class or style list renders no attribute at all.
+        if text.is_empty() && (final_name == "class" || final_name == "style") {
+            return Ok(None);
+        }
+        text
+    };
+
+    Ok(Some(Attribute {
+        name: QualName::new(None, "".into(), final_name.into()),
+        value: text.into(),
+    }))
+}
+
+/// Merge repeated `class` and `style` attributes, such as a static `class` and a `:class` binding.
+fn merge_attributes(attrs: Vec<Attribute>) -> Vec<Attribute> {
+    let mut merged: Vec<Attribute> = vec![];
+
+    for attr in attrs {
+        let separator = match &*attr.name.local {
+            "class" => " ",
+            "style" => "; ",
+            _ => {
+                merged.push(attr);
+                continue;
+            }
+        };
+
+        match merged.iter_mut().find(|existing| existing.name.local == attr.name.local) {
+            Some(existing) => {
+                let before = existing.value.trim_end_matches(|c: char| c == ';' || c.is_whitespace()).to_string();
+                let after = attr.value.trim();
+                existing.value = if before.is_empty() {
+                    after.into()
+                } else {
+                    format!("{}{}{}", before, separator, after).into()
+                };
+            }
+            None => merged.push(attr),
+        }
+    }
 
-    return "(error: stringify_jsvalue unimplemented type!)".to_string();
+    merged
 }
 
 /// Boolean check for a jsval.
@@ -209,7 +594,8 @@ unsafe fn boolify_jsvalue(cx: *mut JSContext, rval: &JSVal) -> bool {
         return true;
     }
 
-    unimplemented!()
+    // anything else (such as a bigint) is treated as truthy.
+    true
 }
 
 /// Evaluate a javascript string in the engine, with respect to a specific global object.
@@ -220,7 +606,7 @@ pub fn eval_in_engine(
     cx: *mut JSContext,
     filename: Option<&str>,
     contents: &str,
-) -> Result<String, String> {
+) -> Result<String, DocgenError> {
     rooted!(in(cx) let mut rval = UndefinedValue());
     let res = rt.evaluate_script(
         global.handle(),
@@ -233,9 +619,9 @@ pub fn eval_in_engine(
     if !res.is_ok() {
         // print_exception(&rt, cx);
         let exception = fmt_exception(&rt, cx);
-        error!("Error: {}", exception);
-        error!("Error: evaluating: {}, {:?}", contents, res);
-        return Err(exception);
+        debug!("Error: evaluating: {}, {:?}", contents, res);
+        unsafe { mozjs::jsapi::JS_ClearPendingException(cx) };
+        return Err(DocgenError::script(exception));
     }
 
     unsafe {
@@ -249,46 +635,143 @@ pub fn eval(
     rt: &Runtime,
     cx: *mut JSContext,
     contents: &str,
-) -> Result<JSVal, ()> {
+) -> Result<JSVal, DocgenError> {
+    eval_at(global, rt, cx, contents, None)
+}
+
+/// Evaluate and return a javascript value, reporting errors at the expression's location in the template.
+pub fn eval_at(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    contents: &str,
+    location: Option<&SourceLocation>,
+) -> Result<JSVal, DocgenError> {
+    let filename = match location.and_then(|location| location.file.as_ref()) {
+        Some(file) => format!("{}", file.display()),
+        None => "docgen_eval".to_string(),
+    };
+    let line = location.map_or(1, |location| location.line as u32);
+
+    eval_in_scope(global, rt, cx, contents, &filename, line).map_err(|(err, _)| err.at(location))
+}
+
+/// Evaluate code in the innermost loop scope, or on the global outside of loops.
+/// `filename` and `line` are where the code was written, for the engine's error messages. Errors come with the
+/// line of the file they were thrown on, when the engine knows it.
+fn eval_in_scope(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    contents: &str,
+    filename: &str,
+    line: u32,
+) -> Result<JSVal, (DocgenError, Option<usize>)> {
+    // inside of a loop, evaluate the expression in the loop's scope.
+    let in_scope = unsafe { in_loop_scope(global, cx) };
+    let scoped;
+    let script = if in_scope {
+        scoped = format!("__docgen.scopes[__docgen.scopes.length - 1]({})", js_string_literal(contents));
+        &scoped
+    } else {
+        contents
+    };
+
     rooted!(in(cx) let mut rval = UndefinedValue());
     let res = rt.evaluate_script(
         global.handle(),
-        contents,
-        "docgen_eval",
-        1,
+        script,
+        filename,
+        line,
         rval.handle_mut(),
     );
 
     if !res.is_ok() {
-        print_exception(&rt, cx);
-        panic!("Error evaluating: {}, {:?}", contents, res);
+        let exception = fmt_exception(&rt, cx);
+        // the engine numbers the lines of code evaluated in a loop scope from 1.
+        let error_line = unsafe { exception_line(cx, filename) }.map(|reported| {
+            if in_scope {
+                line as usize + reported - 1
+            } else {
+                reported
+            }
+        });
+        debug!("Error: evaluating: {}, {:?}", contents, res);
+        unsafe { mozjs::jsapi::JS_ClearPendingException(cx) };
+        return Err((DocgenError::script(exception), error_line));
     }
 
     return Ok(rval.clone());
 }
 
+/// The line the pending exception was thrown on, if it was thrown by code from `filename`.
+unsafe fn exception_line(cx: *mut JSContext, filename: &str) -> Option<usize> {
+    rooted!(in(cx) let mut exc = UndefinedValue());
+    mozjs::rust::jsapi_wrapped::JS_GetPendingException(cx, &mut exc.handle_mut());
+    if !exc.is_object() {
+        return None;
+    }
+    rooted!(in(cx) let exc_object = exc.to_object());
+
+    let file_name = std::ffi::CString::new("fileName").unwrap();
+    rooted!(in(cx) let mut file = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, exc_object.handle(), file_name.as_ptr() as *const i8, file.handle_mut());
+    // code evaluated in a loop scope is named after the file, as `index.html line 12 > eval`.
+    if !file.is_string() || !mozjs::conversions::jsstr_to_string(cx, file.to_string()).starts_with(filename) {
+        return None;
+    }
+
+    let line_name = std::ffi::CString::new("lineNumber").unwrap();
+    rooted!(in(cx) let mut line = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, exc_object.handle(), line_name.as_ptr() as *const i8, line.handle_mut());
+    if line.is_number() && line.to_number() >= 1.0 {
+        Some(line.to_number() as usize)
+    } else {
+        None
+    }
+}
+
+/// Run a static script from `file`, where its code starts on `first_line` (when it's known).
+/// Errors point at the line of the script they were thrown on.
+fn eval_script(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    code: &str,
+    file: Option<&std::path::Path>,
+    first_line: Option<usize>,
+) -> Result<(), DocgenError> {
+    let filename = match file {
+        Some(file) => format!("{}", file.display()),
+        None => "inline_script".to_string(),
+    };
+
+    let start = first_line.unwrap_or(1);
+    eval_in_scope(global, rt, cx, code, &filename, start as u32)
+        .map(|_| ())
+        .map_err(|(err, line)| match first_line {
+            Some(first_line) => {
+                let line = line.unwrap_or(first_line).max(first_line);
+                err.at(Some(&SourceLocation {
+                    file: file.map(std::path::Path::to_path_buf),
+                    line,
+                    column: None,
+                    text: code.lines().nth(line - first_line).unwrap_or("").to_string(),
+                }))
+            }
+            None => err.in_file(file),
+        })
+}
+
 /// Evaluate an expression in the engine, and return whether or not the return value is truthy.
 pub fn eval_in_engine_bool(
     global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
     rt: &Runtime,
     cx: *mut JSContext,
     contents: &str,
-) -> bool {
-    unsafe {
-        rooted!(in(cx) let mut rval = UndefinedValue());
-        let res = rt.evaluate_script(
-            global.handle(),
-            contents,
-            "docgen_eval_bool",
-            1,
-            rval.handle_mut(),
-        );
-        if !res.is_ok() {
-            return false;
-        }
-
-        return boolify_jsvalue(cx, &rval);
-    }
+    location: Option<&SourceLocation>,
+) -> Result<bool, DocgenError> {
+    eval_at(global, rt, cx, contents, location).map(|value| unsafe { boolify_jsvalue(cx, &value) })
 }
 
 /// Flags used for conditional node generation
@@ -409,6 +892,42 @@ fn clone_node_data(data: &NodeData) -> NodeData {
     }
 }
 
+/// A document node holding the nodes of one slot contribution, which can be several (such as a looped element).
+fn new_fragment(nodes: Vec<Rc<Node>>) -> Rc<Node> {
+    Rc::new(Node {
+        parent: Cell::new(None),
+        children: RefCell::new(nodes),
+        data: NodeData::Document,
+    })
+}
+
+fn new_text_node(text: &str) -> Rc<Node> {
+    Rc::new(Node {
+        parent: Cell::new(None),
+        children: RefCell::new(vec![]),
+        data: NodeData::Text {
+            contents: RefCell::new(text.into()),
+        },
+    })
+}
+
+/// Parse html as the contents of a `<body>`, returning the top level nodes.
+fn parse_fragment(html: &str) -> Result<Vec<Rc<Node>>, DocgenError> {
+    let context = QualName::new(None, "http://www.w3.org/1999/xhtml".into(), "body".into());
+    let dom = html5ever::parse_fragment(RcDom::default(), ParseOpts::default(), context, vec![])
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+        .map_err(|err| DocgenError::template(format!("failed to parse html: {}", err)))?;
+
+    // fragments are parsed into an <html> element at the root of the document.
+    let document: &Node = dom.document.borrow();
+    let root = document.children.borrow().first().cloned();
+    Ok(match root {
+        Some(root) => root.children.borrow().clone(),
+        None => vec![],
+    })
+}
+
 fn deep_clone(old_node: &Rc<Node>, parent: Option<Weak<Node>>) -> Rc<Node> {
     use std::borrow::BorrowMut;
     let mut node = Rc::new(Node {
@@ -448,17 +967,21 @@ enum ConditionalChainState {
     NoContinueChain,
     NotConditional
 }
-pub fn parse_for_notation(notation: &str) -> (String, String, String) {
-    let simple_in_of = Regex::new(r###"^([a-zA-Z$_]+) (in|of) (.*)$"###).unwrap();
-
-    match simple_in_of.captures(notation) {
+pub fn parse_for_notation(notation: &str) -> Result<(String, String, String), DocgenError> {
+    // a name, a tuple of names such as `(item, index)`, or a destructuring pattern such as `[key, value]` or `{ title, url }`.
+    let in_of = Regex::new(
+        r###"^\s*([a-zA-Z$_][a-zA-Z0-9$_]*|\([^)]*\)|\[[^\]]*\]|\{[^}]*\})\s+(in|of)\s+(.+?)\s*$"###,
+    )
+    .unwrap();
+
+    match in_of.captures(notation) {
         Some(captures) => {
             let name = &captures[1];
             let kind = &captures[2];
             let expr = &captures[3];
-            return (name.to_string(), kind.to_string(), expr.to_string());
+            return Ok((name.to_string(), kind.to_string(), expr.to_string()));
         }
-        _ => panic!("unsupported for-loop notation: {}", notation),
+        _ => Err(DocgenError::template(format!("unsupported for-loop notation: {}", notation))),
     }
 }
 
@@ -469,6 +992,12 @@ pub struct RenderContext {
     pub contents: Rc<html5ever::rcdom::Node>,
 
     pub children: Vec<Rc<RefCell<RenderContext>>>,
+
+    /// The file this context was rendered from, used to resolve relative includes.
+    pub path: Option<std::path::PathBuf>,
+
+    /// Where the context's nodes were parsed from, for error messages.
+    pub source_map: source::SourceMap,
 }
 
 impl RenderContext {
@@ -481,6 +1010,8 @@ impl RenderContext {
                 slots: std::collections::HashMap::new(),
                 contents,
                 children: vec![context],
+                path: None,
+                source_map: source::SourceMap::default(),
             }
         } else {
             RenderContext::new(contents)
@@ -492,9 +1023,32 @@ impl RenderContext {
             slots: std::collections::HashMap::new(),
             contents,
             children: vec![],
+            path: None,
+            source_map: source::SourceMap::default(),
         }
     }
 
+    /// Find where an expression in a text node was written.
+    fn locate_text(&self, node: &Rc<Node>, expression: &str) -> Option<SourceLocation> {
+        self.source_map
+            .locate_text(node, expression)
+            .map(|location| SourceLocation { file: self.path.clone(), ..location })
+    }
+
+    /// Find where the expression at `offset` in a text node's contents was written.
+    fn locate_in_text(&self, node: &Rc<Node>, text: &str, offset: usize) -> Option<SourceLocation> {
+        self.source_map
+            .locate_in_text(node, text, offset)
+            .map(|location| SourceLocation { file: self.path.clone(), ..location })
+    }
+
+    /// Find where an attribute's expression was written.
+    fn locate_attribute(&self, node: &Rc<Node>, name: &str) -> Option<SourceLocation> {
+        self.source_map
+            .locate_attribute(node, name)
+            .map(|location| SourceLocation { file: self.path.clone(), ..location })
+    }
+
     fn find_slot_contents(&self, slot: &str) -> Vec<Rc<RefCell<RenderContext>>> {
         let mut items: Vec<Rc<RefCell<RenderContext>>> = vec![];
 
@@ -514,6 +1068,285 @@ impl RenderContext {
 
         return items;
     }
+
+    /// Find the innermost contribution to a slot, for a slot without `multiple`. A page's contribution wins over
+    /// those of the layouts wrapping it, and a context's first contribution wins over its later ones.
+    fn find_nearest_slot_contents(&self, slot: &str) -> Option<Rc<RefCell<RenderContext>>> {
+        for child in self.children.iter() {
+            if let Some(item) = RefCell::borrow(child).find_nearest_slot_contents(slot) {
+                return Some(item);
+            }
+        }
+
+        self.slots.get(slot).and_then(|vals| vals.first().cloned())
+    }
+
+    /// The nodes which fill a slot: every contribution (outermost first) when it's `multiple`, otherwise the
+    /// innermost one.
+    fn slot_nodes(&self, slot: &str, multiple: bool) -> Vec<Rc<Node>> {
+        let found_slot_contents = if multiple {
+            self.find_slot_contents(slot)
+        } else {
+            self.find_nearest_slot_contents(slot).into_iter().collect()
+        };
+
+        found_slot_contents
+            .iter()
+            .flat_map(|item| RefCell::borrow(item).contents.children.borrow().clone())
+            .collect()
+    }
+}
+
+/// Set up the global of an included template (a partial or component), which must be the current compartment.
+/// `page` is the global itself, `parent` is the including page's global, and the page's data files are shared.
+unsafe fn init_child_global(
+    cx: *mut JSContext,
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    child_global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+) {
+    assert!(mozjs::rust::wrappers::JS_InitStandardClasses(
+        cx,
+        child_global.handle()
+    ));
+
+    let page_name = std::ffi::CString::new("page").unwrap();
+    let page_ptr = page_name.as_ptr() as *const i8;
+    rooted!(in(cx) let val = mozjs::jsval::ObjectValue(child_global.get()));
+    mozjs::rust::wrappers::JS_SetProperty(
+        cx,
+        child_global.handle(),
+        page_ptr,
+        val.handle(),
+    );
+
+    let result_name = std::ffi::CString::new("parent").unwrap();
+    let result_name_ptr = result_name.as_ptr() as *const i8;
+    rooted!(in(cx) let mut val = mozjs::jsval::ObjectValue(global.get()));
+    mozjs::rust::wrappers::JS_WrapValue(cx, val.handle_mut());
+    mozjs::rust::wrappers::JS_SetProperty(
+        cx,
+        child_global.handle(),
+        result_name_ptr,
+        val.handle(),
+    );
+
+    // partials share the page's data files.
+    let data_name = std::ffi::CString::new("data").unwrap();
+    let data_name_ptr = data_name.as_ptr() as *const i8;
+    rooted!(in(cx) let mut data = UndefinedValue());
+    {
+        // the page's global is read from its own compartment, then the value is wrapped for the child's.
+        let _ac = mozjs::jsapi::JSAutoCompartment::new(cx, global.get());
+        mozjs::rust::wrappers::JS_GetProperty(
+            cx,
+            global.handle(),
+            data_name_ptr,
+            data.handle_mut(),
+        );
+    }
+    mozjs::rust::wrappers::JS_WrapValue(cx, data.handle_mut());
+    mozjs::rust::wrappers::JS_SetProperty(
+        cx,
+        child_global.handle(),
+        data_name_ptr,
+        data.handle(),
+    );
+}
+
+/// Only pages (and their layouts) are wrapped in a layout, so a partial or component which sets one (with
+/// `<layout src>`, say) is an error instead of being ignored.
+unsafe fn check_no_layout(
+    cx: *mut JSContext,
+    child_global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    path: &std::path::Path,
+) -> Result<(), DocgenError> {
+    match get_string_property(cx, child_global, "layout") {
+        Some(layout) => Err(DocgenError::template(format!(
+            "only pages can have a layout, but this partial or component sets it to {}",
+            layout
+        ))
+        .in_file(Some(path))),
+        None => Ok(()),
+    }
+}
+
+/// The children of a parsed document's `<body>`.
+fn body_children(dom: &RcDom) -> Vec<Rc<Node>> {
+    let document: &Node = dom.document.borrow();
+    let html = document.children.borrow().last().cloned();
+    let body = html.and_then(|html| html.children.borrow().last().cloned());
+    match body {
+        Some(body) => body.children.borrow().clone(),
+        None => vec![],
+    }
+}
+
+/// Whether a node is a text node containing only whitespace.
+fn is_whitespace(node: &Rc<Node>) -> bool {
+    match &node.data {
+        NodeData::Text { contents } => contents.borrow().trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// Convert a kebab-case attribute name to a camelCase prop name, so `post-title` becomes `postTitle`.
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('-');
+    let mut camel = parts.next().unwrap_or("").to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// Render a component: a template whose `props` global holds the attributes it was given, and whose slots are filled
+/// by the children of the element which included it.
+unsafe fn render_component(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    node: &Rc<Node>,
+    component_path: &std::path::Path,
+    props: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    render_context: Rc<RefCell<RenderContext>>,
+    slot_contents: Rc<Option<html5ever::rcdom::RcDom>>,
+    options: &RenderOptions,
+) -> Result<Vec<Rc<Node>>, DocgenError> {
+    options.mark_included(component_path);
+    let mut contents = std::fs::read_to_string(component_path)
+        .map_err(|err| DocgenError::io(component_path, err))?;
+
+    // 1. Render the children where they're written. Those with a `slot` attribute fill the component's named
+    // slots, and the rest fill its default slot.
+    let caller_slots = std::mem::replace(&mut render_context.borrow_mut().slots, std::collections::HashMap::new());
+    let children = render_child_list(global, rt, cx, node, render_context.clone(), slot_contents, options);
+    let mut slots = std::mem::replace(&mut render_context.borrow_mut().slots, caller_slots);
+    let children = children?;
+
+    if children.iter().any(|child| !is_whitespace(child)) {
+        slots
+            .entry("default".to_string())
+            .or_insert_with(Vec::new)
+            .push(Rc::new(RefCell::new(RenderContext::new(new_fragment(children)))));
+    }
+
+    let mut slot_context = RenderContext::new(node.clone());
+    slot_context.slots = slots;
+    let slot_context = Rc::new(RefCell::new(slot_context));
+
+    // 2. Render the component in its own global.
+    rooted!(in(cx) let child_global =
+    JS_NewGlobalObject(cx, &SIMPLE_GLOBAL_CLASS, ptr::null_mut(),
+                                OnNewGlobalHookOption::FireOnNewGlobalHook,
+                                &CompartmentOptions::default())
+    );
+
+    let _ac = mozjs::jsapi::JSAutoCompartment::new(cx, child_global.get());
+    init_child_global(cx, global, &child_global);
+
+    let props_name = std::ffi::CString::new("props").unwrap();
+    rooted!(in(cx) let mut props_value = mozjs::jsval::ObjectValue(props.get()));
+    mozjs::rust::wrappers::JS_WrapValue(cx, props_value.handle_mut());
+    mozjs::rust::wrappers::JS_SetProperty(
+        cx,
+        child_global.handle(),
+        props_name.as_ptr() as *const i8,
+        props_value.handle(),
+    );
+
+    let (component, component_context) = parse_and_render_dom(
+        &child_global,
+        &rt,
+        cx,
+        &mut contents,
+        None,
+        Some(slot_context.clone()),
+        std::rc::Rc::new(None),
+        Some(component_path),
+        Some(1),
+        options,
+    )?;
+    check_no_layout(cx, &child_global, component_path)?;
+
+    // 3. The component's own slot contributions (to the page's layout, say) are passed on, except the caller's.
+    component_context.borrow_mut().children.retain(|child| !Rc::ptr_eq(child, &slot_context));
+    render_context.borrow_mut().children.push(component_context);
+
+    Ok(body_children(&component)
+        .iter()
+        .map(|child| deep_clone(child, Some(Rc::downgrade(node))))
+        .collect())
+}
+
+/// Render the children of an element, returning the nodes which should replace them.
+unsafe fn render_child_list(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    node: &Rc<Node>,
+    render_context: Rc<RefCell<RenderContext>>,
+    slot_contents: Rc<Option<html5ever::rcdom::RcDom>>,
+    options: &RenderOptions,
+) -> Result<Vec<Rc<Node>>, DocgenError> {
+    let mut out_children: Vec<Rc<Node>> = vec![];
+
+    let mut children = node.children.borrow_mut();
+    trace!("have {} children.", children.len());
+    let mut iter_continue = false;
+    let mut last_was_removed = false;
+
+    for item in children.iter_mut() {
+        trace!("Rendering child...");
+        let flags = render_children(
+            global,
+            rt,
+            cx,
+            item,
+            true,
+            render_context.clone(),
+            slot_contents.clone(),
+            iter_continue,
+            last_was_removed,
+            options,
+        )?;
+
+        // use the conditional flags to decide if we need to keep checking conditionals
+        match flags.conditional {
+            ConditionalChainState::NotConditional |
+            ConditionalChainState::NoContinueChain => {
+                iter_continue = false;
+            },
+            ConditionalChainState::CondinuedNotFound |
+            ConditionalChainState::StartedNotFound => {
+                iter_continue = true;
+            }
+        }
+
+        // flag for if last was removed (used to trim unneeded whitespace text nodes surrounding removed elements)
+        if flags.remove && flags.replace.is_none() {
+            last_was_removed = true
+        } else {
+            last_was_removed = false;
+        }
+
+        // If not removed, add it to output
+        if !flags.remove {
+            out_children.push(item.clone())
+        }
+
+        // If there's replacements, add them.
+        if let Some(replacements) = flags.replace {
+            for item in replacements {
+                out_children.push(item);
+            }
+        }
+    }
+
+    Ok(out_children)
 }
 
 /// Render the children of a node, recursively.
@@ -526,8 +1359,9 @@ unsafe fn render_children(
     render_context: Rc<RefCell<RenderContext>>,
     slot_contents: Rc<Option<html5ever::rcdom::RcDom>>,
     conditional_chain_continue: bool,
-    last_was_removed: bool
-) -> CondGenFlags {
+    last_was_removed: bool,
+    options: &RenderOptions,
+) -> Result<CondGenFlags, DocgenError> {
     let flags = CondGenFlags::default();
 
     match node.data.borrow() {
@@ -544,14 +1378,16 @@ unsafe fn render_children(
                     render_context.clone(),
                     slot_contents.clone(),
                     true,
-                    false
-                );
+                    false,
+                    options,
+                )?;
             }
 
-            return CondGenFlags::default();
+            return Ok(CondGenFlags::default());
         }
         NodeData::Element { name, attrs, .. } => {
             let node_name = name.local.to_string();
+            let current_path = RefCell::borrow(&render_context).path.clone();
             debug!("-> enter element: {:?}", node_name);
 
             if node_name == "h1"
@@ -576,30 +1412,42 @@ unsafe fn render_children(
             if name.local.to_string() == "script" {
                 if get_attribute(&node, "static").is_some() {
                     let script = inner_text(node);
-                    eval_in_engine(&global, &rt, cx, Some("inline_script"), &script);
+                    let location = RefCell::borrow(&render_context).locate_text(node, script.trim());
+                    // the code starts on the line the script is written on, less any blank lines before it.
+                    let leading = &script[..script.len() - script.trim_start().len()];
+                    let first_line = location
+                        .as_ref()
+                        .map(|location| location.line.saturating_sub(leading.matches('\n').count()).max(1));
+                    // static scripts run in the current loop scope, so they can use (and declare) the loop's variables.
+                    eval_script(&global, &rt, cx, &script, current_path.as_ref().map(|path| path.as_path()), first_line)?;
 
                     // If it has a src="" attribute, load to string and execute.
                     if let Some(script_src) = get_attribute(&node, "src") {
-                        let loaded_script_file =
-                            std::fs::read_to_string(std::path::Path::new(&script_src)).unwrap();
-                        eval_in_engine(&global, &rt, cx, Some(&script_src), &loaded_script_file);
+                        let script_path = options.resolve(&script_src, current_path.as_ref().map(|path| path.as_path()));
+                        let loaded_script_file = std::fs::read_to_string(&script_path)
+                            .map_err(|err| DocgenError::io(&script_path, err))?;
+                        eval_script(&global, &rt, cx, &loaded_script_file, Some(&script_path), Some(1))?;
                     }
 
-                    return CondGenFlags {
+                    return Ok(CondGenFlags {
                         conditional: ConditionalChainState::NotConditional,
                         remove: true,
                         replace: None,
-                    };
+                    });
                 }
             }
 
             let mut needs_expansion: Option<String> = None;
+            let mut loop_location: Option<SourceLocation> = None;
             let mut replacements: Vec<Rc<Node>> = vec![];
             let mut needs_remove: bool = false;
             let mut needs_fork: bool = false;
+            let mut content_children: Option<Vec<Rc<Node>>> = None;
             let mut final_attrs: Vec<html5ever::interface::Attribute> = vec![];
             let mut loop_name = get_attribute(node, "x-as").unwrap_or("item".to_string());
-            let mut index_name = get_attribute(node, "x-index").unwrap_or("i".to_string());
+            let mut loop_kind = "of".to_string();
+            let mut empty_loop_state = ConditionalChainState::StartedNotFound;
+            let index_name = get_attribute(node, "x-index").unwrap_or("i".to_string());
             debug!(
                 "{}: slot={:?}, name={:?}",
                 node_name,
@@ -609,88 +1457,155 @@ unsafe fn render_children(
             if let Some(val) = get_attribute(node, "slot") {
                 debug!("found element named {} to add to {}", node_name, val);
 
-                {
-                    let mut inner = render_context.borrow_mut();
-                    inner
-                        .slots
-                        .entry(val)
-                        .or_insert_with(Vec::new)
-                        .push(Rc::new(RefCell::new(RenderContext::new(node.clone()))));
+                // contributions are rendered where they're written, so they can use the variables in scope there.
+                attrs.borrow_mut().retain(|attr| &*attr.name.local != "slot");
+                let flags = render_children(
+                    global,
+                    rt,
+                    cx,
+                    node,
+                    loop_expansion,
+                    render_context.clone(),
+                    slot_contents.clone(),
+                    conditional_chain_continue,
+                    last_was_removed,
+                    options,
+                )?;
+
+                let mut contributions = vec![];
+                if !flags.remove {
+                    contributions.push(node.clone());
+                }
+                if let Some(replace) = flags.replace {
+                    contributions.extend(replace);
                 }
 
-                return CondGenFlags {
-                    conditional: ConditionalChainState::NotConditional,
+                // the element and its loop's copies are one contribution, so a slot takes all of them or none.
+                if contributions.len() > 0 {
+                    let contribution = Rc::new(RefCell::new(RenderContext::new(new_fragment(contributions))));
+                    render_context.borrow_mut().slots.entry(val).or_insert_with(Vec::new).push(contribution);
+                }
+
+                // the contribution still takes part in an x-if/x-else chain where it's written.
+                return Ok(CondGenFlags {
+                    conditional: flags.conditional,
                     remove: true,
                     replace: None,
-                };
-            } else if (node_name == "slot" && get_attribute(node, "name").is_some()) {
-                let slot_name = get_attribute(node, "name").unwrap();
-
-                if slot_name == "content" {
-                    if let Some(contents) = slot_contents.clone().borrow() {
-                        trace!("swapping slot contents into dom tree.");
-                        let doc: &Node = contents.document.borrow();
-                        let children: Ref<Vec<Rc<Node>>> = doc.children.borrow();
-                        trace!("got {} childen to doc", children.len());
-                        if children.len() == 0 {
-                            return CondGenFlags {
-                                conditional: ConditionalChainState::NotConditional,
-                                remove: true,
-                                replace: None,
-                            };
-                        }
-                        let html: &Node = children[children.len() - 1].borrow();
-                        let html_children = html.children.borrow();
-                        trace!("got {} childen to html", html_children.len());
-                        if html_children.len() == 0 {
-                            return CondGenFlags {
-                                conditional: ConditionalChainState::NotConditional,
-                                remove: true,
-                                replace: None,
-                            };
+                });
+            } else if node_name == "slot" && get_attribute(node, "src").is_none() {
+                // a slot without a name is a component's default slot.
+                let slot_name = get_attribute(node, "name").unwrap_or("default".to_string());
+
+                let contributions: Vec<Rc<Node>> = if slot_name == "content" {
+                    match slot_contents.clone().borrow() {
+                        Some(contents) => {
+                            trace!("swapping slot contents into dom tree.");
+                            body_children(contents)
+                                .iter()
+                                .map(|cn| deep_clone(cn, Some(std::rc::Rc::downgrade(node))))
+                                .collect()
                         }
-                        let body: &Node = html_children[html_children.len() - 1].borrow();
-                        let children = body.children.borrow();
-                        // let items : &Vec<std::rc::Rc<html5ever::rcdom::Node>> = children.as_ref();
-                        return CondGenFlags {
-                            conditional: ConditionalChainState::NotConditional,
-                            remove: true,
-                            replace: Some(
-                                children
-                                    .iter()
-                                    .map(|cn| deep_clone(cn, Some(std::rc::Rc::downgrade(node))))
-                                    .collect(),
-                            ),
-                        };
+                        None => vec![],
                     }
                 } else {
-                    // let slot : &RenderContext = ;
-                    let found_slot_contents =
-                        render_context.borrow_mut().find_slot_contents(&slot_name);
+                    // a slot takes every contribution when it's marked `multiple`, otherwise only the nearest one.
+                    let multiple = get_attribute(node, "multiple").is_some();
+                    RefCell::borrow(&render_context).slot_nodes(&slot_name, multiple)
+                };
 
-                    if found_slot_contents.len() > 0 {
-                        debug!("-> found slot contents: {}", &slot_name);
-                        // let items : &Vec<std::rc::Rc<html5ever::rcdom::Node>> = children.as_ref();
-                        return CondGenFlags {
-                            conditional: ConditionalChainState::NotConditional,
+                if contributions.len() > 0 {
+                    debug!("-> found slot contents: {}", &slot_name);
+                    return Ok(CondGenFlags {
+                        conditional: ConditionalChainState::NotConditional,
+                        remove: true,
+                        replace: Some(contributions),
+                    });
+                }
+
+                // nothing was contributed to the slot, so its own children are rendered instead.
+                let fallback = render_child_list(global, rt, cx, node, render_context.clone(), slot_contents.clone(), options)?;
+                return Ok(CondGenFlags {
+                    conditional: ConditionalChainState::NotConditional,
+                    remove: true,
+                    replace: if fallback.is_empty() { None } else { Some(fallback) },
+                });
+            } else if node_name == "layout" {
+                // <layout src> is the same as setting the `layout` variable, and is replaced by its children
+                // (which are usually contributions to the layout's slots).
+                let layout = get_attribute(node, "src").ok_or_else(|| {
+                    let location = RefCell::borrow(&render_context).locate_text(node, "<layout");
+                    DocgenError::template("<layout> must have a src attribute".to_string()).at(location.as_ref())
+                })?;
+                set_global_value(cx, global, "layout", &serde_json::Value::String(layout));
+
+                let children = render_child_list(global, rt, cx, node, render_context.clone(), slot_contents.clone(), options)?;
+                return Ok(CondGenFlags {
+                    conditional: ConditionalChainState::NotConditional,
+                    remove: true,
+                    replace: if children.is_empty() { None } else { Some(children) },
+                });
+            }
+
+            // a loop is expanded before anything else, so its other attributes are evaluated for each item.
+            if loop_expansion {
+                if let Some(script) = get_attribute(node, "x-for") {
+                    loop_location = RefCell::borrow(&render_context).locate_attribute(node, "x-for");
+                    let (new_loop_name, kind, expression) =
+                        parse_for_notation(&script).map_err(|err| err.at(loop_location.as_ref()))?;
+                    loop_name = new_loop_name;
+                    loop_kind = kind;
+                    needs_expansion = Some(expression);
+                } else if let Some(script) = get_attribute(node, "x-each") {
+                    loop_location = RefCell::borrow(&render_context).locate_attribute(node, "x-each");
+                    needs_expansion = Some(script);
+                }
+            }
+
+            // x-else and x-else-if on a loop decide whether the whole loop renders. (x-if filters each item.)
+            if needs_expansion.is_some() {
+                let else_if = get_attribute(node, "x-else-if");
+                if else_if.is_some() || get_attribute(node, "x-else").is_some() {
+                    if !conditional_chain_continue {
+                        trace!("not rendering else loop: no continue!");
+                        return Ok(CondGenFlags {
+                            conditional: ConditionalChainState::NoContinueChain,
                             remove: true,
-                            replace: Some(
-                                found_slot_contents
-                                    .iter()
-                                    .map(|v| v.borrow_mut().contents.clone())
-                                    .collect(),
-                            ),
-                        };
-                    } else {
-                        return CondGenFlags {
-                            conditional: ConditionalChainState::NotConditional,
+                            replace: None,
+                        });
+                    }
+                    empty_loop_state = ConditionalChainState::CondinuedNotFound;
+                }
+
+                if let Some(script) = else_if {
+                    let location = RefCell::borrow(&render_context).locate_attribute(node, "x-else-if");
+                    let included = options.recover(eval_in_engine_bool(&global, &rt, cx, &script, location.as_ref()), false)?;
+                    if !included {
+                        trace!("not rendering else-if loop: falsey flags!");
+                        return Ok(CondGenFlags {
+                            conditional: ConditionalChainState::CondinuedNotFound,
                             remove: true,
                             replace: None,
-                        };
+                        });
                     }
                 }
             }
-            {
+
+            // a component's attributes are its props, which are evaluated here and passed to it as an object.
+            // custom elements (such as <post-card>) which match a file in the components directory are components too.
+            let component_path = if needs_expansion.is_some() {
+                None
+            } else if node_name == "component" {
+                get_attribute(node, "src").map(|src| options.resolve(&src, current_path.as_ref().map(|path| path.as_path())))
+            } else {
+                options.find_component(&node_name)
+            };
+            rooted!(in(cx) let props = if component_path.is_some() {
+                mozjs::jsapi::JS_NewPlainObject(cx)
+            } else {
+                ptr::null_mut()
+            });
+
+            if needs_expansion.is_none() {
                 let mut attributes: RefMut<Vec<html5ever::interface::Attribute>> =
                     attrs.borrow_mut();
 
@@ -698,74 +1613,88 @@ unsafe fn render_children(
                     trace!("{:?}", attr);
                     let name = &attr.name.local.to_string();
                     let script = String::from(&attr.value);
-                    if name.starts_with(":") && needs_expansion.is_none() {
-                        let final_name = name[1..].to_string();
-                        attr.name = QualName::new(None, "".into(), final_name.clone().into());
-                        let value: JSVal = eval(&global, &rt, cx, &script).unwrap();
-
-                        // support for boolean flag type attribute names
-                        if final_name == "checked" // checkbox
-                            || final_name == "selected" // select box
-                            || final_name == "disabled" // input
-                            || final_name == "readonly" // input 
-                            || final_name == "autoplay" // video
-                            || final_name == "controls" // video
-                            || final_name == "loop" // video 
-                            || final_name == "muted" // video
-                            || final_name == "open" // summary
-                        {
-                            // only if it's a boolean, perform boolification of it for the conditional
-                            if boolify_jsvalue(cx, &value) {
-                                attr.value = String::new().into();
-                                final_attrs.push(attr.clone());
+                    if component_path.is_some() && !(node_name == "component" && name == "src") && !name.starts_with("x-") {
+                        let value: JSVal = if name.starts_with(":") {
+                            let location = RefCell::borrow(&render_context).locate_attribute(node, name);
+                            // in lenient mode, a prop which fails to evaluate is left out.
+                            match options.recover(eval_at(&global, &rt, cx, &script, location.as_ref()).map(Some), None)? {
+                                Some(value) => value,
+                                None => continue,
                             }
                         } else {
-                            attr.value = stringify_jsvalue(cx, &value).into();
-                            final_attrs.push(attr.clone());
+                            rooted!(in(cx) let mut text = UndefinedValue());
+                            script.to_jsval(cx, text.handle_mut());
+                            text.get()
+                        };
+
+                        rooted!(in(cx) let value = value);
+                        let prop_name = std::ffi::CString::new(camel_case(name.trim_start_matches(':'))).unwrap();
+                        mozjs::rust::wrappers::JS_SetProperty(
+                            cx,
+                            props.handle(),
+                            prop_name.as_ptr() as *const i8,
+                            value.handle(),
+                        );
+                    } else if name.starts_with(":") {
+                        let location = RefCell::borrow(&render_context).locate_attribute(node, name);
+                        if let Some(bound) = bind_attribute(&global, &rt, cx, name, &script, location.as_ref(), options)? {
+                            final_attrs.push(bound);
                         }
-                    } else if name == "x-if" && needs_expansion.is_none() {
-                        let included = eval_in_engine_bool(&global, &rt, cx, &script);
+                    } else if name == "x-if" {
+                        let location = RefCell::borrow(&render_context).locate_attribute(node, name);
+                        let included = options.recover(eval_in_engine_bool(&global, &rt, cx, &script, location.as_ref()), false)?;
                         if !included {
-                            return CondGenFlags {
+                            return Ok(CondGenFlags {
                                 conditional: ConditionalChainState::StartedNotFound,
                                 remove: true,
                                 replace: None,
-                            };
+                            });
                         }
-                    } else if name == "x-else-if" && needs_expansion.is_none() {
+                    } else if name == "x-else-if" {
                         if conditional_chain_continue {
-                            let included = eval_in_engine_bool(&global, &rt, cx, &script);
+                            let location = RefCell::borrow(&render_context).locate_attribute(node, name);
+                            let included = options.recover(eval_in_engine_bool(&global, &rt, cx, &script, location.as_ref()), false)?;
                             if !included {
                                 trace!("not rendering else-if: falsey flags!");
-                                return CondGenFlags {
+                                return Ok(CondGenFlags {
                                     conditional: ConditionalChainState::CondinuedNotFound,
                                     remove: true,
                                     replace: None,
-                                };
+                                });
                             }
                         } else {
                             trace!("not rendering else-if: no continue!");
-                            return CondGenFlags {
+                            return Ok(CondGenFlags {
                                 conditional: ConditionalChainState::NoContinueChain,
                                 remove: true,
                                 replace: None
-                            }
+                            })
                         }
-                    } else if name == "x-else" && needs_expansion.is_none() {
+                    } else if name == "x-else" {
                         if !conditional_chain_continue {
                             trace!("not rendering else: no continue!");
-                            return CondGenFlags {
+                            return Ok(CondGenFlags {
                                 conditional: ConditionalChainState::NoContinueChain,
                                 remove: true,
                                 replace: None
-                            }
+                            })
                         }
-                    } else if name == "x-each" && loop_expansion {
-                        needs_expansion = Some(script);
-                    } else if name == "x-for" && loop_expansion {
-                        let (new_loop_name, _, expression) = parse_for_notation(&script);
-                        loop_name = new_loop_name;
-                        needs_expansion = Some(expression);
+                    } else if name == "x-html" || name == "x-text" || name == "x-markdown" {
+                        let location = RefCell::borrow(&render_context).locate_attribute(node, name);
+                        let text = eval_at(&global, &rt, cx, &script, location.as_ref()).and_then(|value| {
+                            if value.is_null_or_undefined() {
+                                Ok(String::new())
+                            } else {
+                                try_stringify_jsvalue(&global, &rt, cx, &value).map_err(|err| err.at(location.as_ref()))
+                            }
+                        });
+                        // in lenient mode, content which fails to evaluate leaves the element empty.
+                        let text = options.recover(text, String::new())?;
+                        content_children = Some(match name.as_str() {
+                            "x-text" => vec![new_text_node(&text)],
+                            "x-markdown" => parse_fragment(&render::render_markdown(&text))?,
+                            _ => parse_fragment(&text)?,
+                        });
                     } else if node_name == "slot" && name == "src" {
                         rooted!(in(cx) let child_global =
                         JS_NewGlobalObject(cx, &SIMPLE_GLOBAL_CLASS, ptr::null_mut(),
@@ -774,33 +1703,12 @@ unsafe fn render_children(
                         );
 
                         let _ac = mozjs::jsapi::JSAutoCompartment::new(cx, child_global.get());
-                        assert!(mozjs::rust::wrappers::JS_InitStandardClasses(
-                            cx,
-                            global.handle()
-                        ));
-
-                        let page_name = std::ffi::CString::new("page").unwrap();
-                        let page_ptr = page_name.as_ptr() as *const i8;
-                        rooted!(in(cx) let val = mozjs::jsval::ObjectValue(child_global.get()));
-                        mozjs::rust::wrappers::JS_SetProperty(
-                            cx,
-                            child_global.handle(),
-                            page_ptr,
-                            val.handle(),
-                        );
-
-                        let result_name = std::ffi::CString::new("parent").unwrap();
-                        let result_name_ptr = result_name.as_ptr() as *const i8;
-                        rooted!(in(cx) let val = mozjs::jsval::ObjectValue(global.get()));
-                        mozjs::rust::wrappers::JS_SetProperty(
-                            cx,
-                            child_global.handle(),
-                            result_name_ptr,
-                            val.handle(),
-                        );
+                        init_child_global(cx, global, &child_global);
 
-                        let mut contents =
-                            std::fs::read_to_string(std::path::Path::new(&script)).unwrap();
+                        let partial_path = options.resolve(&script, current_path.as_ref().map(|path| path.as_path()));
+                        options.mark_included(&partial_path);
+                        let mut contents = std::fs::read_to_string(&partial_path)
+                            .map_err(|err| DocgenError::io(&partial_path, err))?;
                         let (partial, child_render_context) = parse_and_render_dom(
                             &child_global,
                             &rt,
@@ -809,7 +1717,11 @@ unsafe fn render_children(
                             None,
                             None,
                             std::rc::Rc::new(None),
-                        );
+                            Some(partial_path.as_path()),
+                            Some(1),
+                            options,
+                        )?;
+                        check_no_layout(cx, &child_global, &partial_path)?;
 
                         {
                             let ccx = render_context.clone();
@@ -834,7 +1746,7 @@ unsafe fn render_children(
                         // node.children.swap(&body_children);
                         let children = body.children.borrow();
                         // let items : &Vec<std::rc::Rc<html5ever::rcdom::Node>> = children.as_ref();
-                        return CondGenFlags {
+                        return Ok(CondGenFlags {
                             conditional: ConditionalChainState::NotConditional,
                             remove: true,
                             replace: Some(
@@ -843,7 +1755,7 @@ unsafe fn render_children(
                                     .map(|cn| deep_clone(cn, Some(std::rc::Rc::downgrade(node))))
                                     .collect(),
                             ),
-                        };
+                        });
                     } else if name == "x-as" || name == "x-index" {
                         // Do nothing.
                     } else {
@@ -852,232 +1764,139 @@ unsafe fn render_children(
                 }
             }
 
-            attrs.replace(final_attrs);
-
-            if let Some(script) = needs_expansion {
-                if loop_expansion {
-                    trace!("begin loop expansion on {:?}", name);
-                    // 0. Run the script and setup replacement list.
-                    let object = eval(&global, &rt, cx, &script).unwrap();
-
-                    // 1. Symbol.Iterator
-                    let sym = mozjs::jsapi::GetWellKnownSymbol(cx, jsapi::SymbolCode::iterator);
-                    let id = mozjs::glue::RUST_SYMBOL_TO_JSID(sym);
-
-                    if !object.is_object() {
-                        trace!("eval returned non-object!");
-                    }
-
-                    // 2. object[Symbol.iterator]
-                    rooted!(in(cx) let mut fnhandle = UndefinedValue());
-                    rooted!(in(cx) let obj = object.to_object());
-                    if !mozjs::rust::wrappers::JS_GetPropertyById(
-                        cx,
-                        obj.handle(),
-                        mozjs::rust::Handle::new(&id),
-                        fnhandle.handle_mut(),
-                    ) {
-                        panic!("Couldnt get iterator")
-                    }
-
-                    // 3. Call function to get an iterator.
-                    rooted!(in(cx) let mut iterret = UndefinedValue());
-                    rooted!(in(cx) let func = mozjs::rust::wrappers::JS_ValueToFunction(cx, fnhandle.handle()));
-                    let args = mozjs::jsapi::HandleValueArray::new();
-                    mozjs::rust::wrappers::JS_CallFunction(
-                        cx,
-                        obj.handle(),
-                        func.handle(),
-                        &args,
-                        iterret.handle_mut(),
-                    );
-
-                    let mut loop_index: u64 = 0;
-
-                    loop {
-                        needs_remove = true;
-                        trace!("iterating thing...");
-                        // 4. Call the iterator.next() function
-                        rooted!(in(cx) let returnvalue = UndefinedValue());
-                        rooted!(in(cx) let iter_result = iterret.to_object());
-                        let next_str = std::ffi::CString::new("next").unwrap();
-                        let next_ptr = next_str.as_ptr() as *const i8;
-                        let args = mozjs::jsapi::HandleValueArray::new();
-                        rooted!(in(cx) let mut iteration_value = UndefinedValue());
-                        mozjs::rust::wrappers::JS_CallFunctionName(
-                            cx,
-                            iter_result.handle(),
-                            next_ptr,
-                            &args,
-                            iteration_value.handle_mut(),
-                        );
+            // a loop's element is copied for each item with its attributes as written.
+            if needs_expansion.is_none() {
+                attrs.replace(merge_attributes(final_attrs));
+            }
 
-                        if !iteration_value.is_object() {
-                            trace!("iteration is not obj");
-                            break;
-                        }
+            // x-html, x-text and x-markdown replace the element's children, which aren't rendered as a template.
+            let has_content_children = content_children.is_some();
+            if let Some(content_children) = content_children {
+                for child in content_children.iter() {
+                    child.parent.set(Some(Rc::downgrade(node)));
+                }
+                node.children.replace(content_children);
+            }
 
-                        // 5. Get result.value
-                        rooted!(in(cx) let mut iteration_result_value = UndefinedValue());
-
-                        if iteration_value.is_object() {
-                            let c_str = std::ffi::CString::new("value").unwrap();
-                            let ptr = c_str.as_ptr() as *const i8;
-                            rooted!(in(cx) let iteration_value_obj = iteration_value.to_object());
-                            mozjs::rust::wrappers::JS_GetProperty(
-                                cx,
-                                iteration_value_obj.handle(),
-                                ptr,
-                                iteration_result_value.handle_mut(),
-                            );
-                        }
+            if let Some(component_path) = component_path {
+                let replace = render_component(
+                    global,
+                    rt,
+                    cx,
+                    node,
+                    &component_path,
+                    &props,
+                    render_context.clone(),
+                    slot_contents.clone(),
+                    options,
+                )?;
 
-                        // debug!("iter value -> {}", stringify_jsvalue(cx, &iteration_result_value));
+                return Ok(CondGenFlags {
+                    conditional: ConditionalChainState::NoContinueChain,
+                    remove: true,
+                    replace: Some(replace),
+                });
+            }
 
-                        // 6. Check if iterator is done
-                        let done_str = std::ffi::CString::new("done").unwrap();
-                        let done_str_ptr = done_str.as_ptr() as *const i8;
-                        rooted!(in(cx) let iteration_value_obj = iteration_value.to_object());
-                        rooted!(in(cx) let mut iteration_done_value = UndefinedValue());
-                        mozjs::rust::wrappers::JS_GetProperty(
-                            cx,
-                            iteration_value_obj.handle(),
-                            done_str_ptr,
-                            iteration_done_value.handle_mut(),
-                        );
-                        trace!(
-                            "iter done -> {}",
-                            stringify_jsvalue(cx, &iteration_done_value)
-                        );
+            // a <component> without a src has nothing to render, so (in lenient mode) it's left out.
+            if node_name == "component" && needs_expansion.is_none() {
+                let location = RefCell::borrow(&render_context).locate_text(node, "<component");
+                options.recover(
+                    Err(DocgenError::template("<component> requires a src attribute".to_string()).at(location.as_ref())),
+                    (),
+                )?;
+                return Ok(CondGenFlags {
+                    conditional: ConditionalChainState::NoContinueChain,
+                    remove: true,
+                    replace: None,
+                });
+            }
 
-                        // Boolify javascript
-                        if boolify_jsvalue(cx, &iteration_done_value) {
-                            trace!("is done. return.");
-                            break;
-                        }
+            if let Some(script) = needs_expansion {
+                trace!("begin loop expansion on {:?}", name);
+                if node_name == "slot" && get_attribute(node, "src").is_some() {
+                    return Err(DocgenError::template("cannot set slot src inside a loop".to_string()).at(loop_location.as_ref()));
+                }
 
-                        // HACK: Store the value temporarily in the global scope.
-                        // TODO: iterate and create new scopes with the values.
-                        let result_name = std::ffi::CString::new(loop_name.clone()).unwrap();
-                        let result_name_ptr = result_name.as_ptr() as *const i8;
-                        rooted!(in(cx) let iter_val = iteration_result_value.clone());
-                        mozjs::rust::wrappers::JS_SetProperty(
-                            cx,
-                            global.handle(),
-                            result_name_ptr,
-                            iter_val.handle(),
-                        );
+                // 0. Run the script.
+                let object = match options.recover(eval_at(&global, &rt, cx, &script, loop_location.as_ref()).map(Some), None)? {
+                    Some(object) => object,
+                    // in lenient mode, a loop which fails to evaluate renders nothing.
+                    None => eval(&global, &rt, cx, "[]")?,
+                };
 
-                        let index_name_str = std::ffi::CString::new(index_name.clone()).unwrap();
-                        let index_name_ptr = index_name_str.as_ptr() as *const i8;
-                        rooted!(in(cx) let mut index = UndefinedValue());
-                        loop_index.to_jsval(cx, index.handle_mut());
-                        mozjs::rust::wrappers::JS_SetProperty(
-                            cx,
-                            global.handle(),
-                            index_name_ptr,
-                            index.handle(),
-                        );
+                // 1. Collect the items into an array of entries, and name the parts of each entry.
+                let single = !loop_name.starts_with('(');
+                let params = if single {
+                    format!("{}, {}", loop_name, index_name)
+                } else {
+                    loop_name[1..loop_name.len() - 1].to_string()
+                };
+                let iterate = format!("({})({}, {}, __docgen.value)", ITERATE_SCRIPT, js_string_literal(&loop_kind), single);
+                let entries = eval_with_value(&global, &rt, cx, &object, &iterate)
+                    .map_err(|_| DocgenError::template(format!("{} is not iterable", script)).at(loop_location.as_ref()))?;
+                rooted!(in(cx) let entries = entries.to_object());
+
+                let length_name = std::ffi::CString::new("length").unwrap();
+                rooted!(in(cx) let mut length = UndefinedValue());
+                mozjs::rust::wrappers::JS_GetProperty(
+                    cx,
+                    entries.handle(),
+                    length_name.as_ptr() as *const i8,
+                    length.handle_mut(),
+                );
 
-                        // Mut and render
-                        let x = node.parent.replace(None);
-                        let parent: Option<Weak<Node>>;
-                        if let Some(x) = x {
-                            parent = Some(x.clone());
-                        } else {
-                            parent = None;
-                        }
+                for index in 0..length.to_int32() as u32 {
+                    rooted!(in(cx) let mut entry = UndefinedValue());
+                    mozjs::rust::wrappers::JS_GetElement(cx, entries.handle(), index, entry.handle_mut());
+
+                    // 2. Copy the element, without the attributes which made it a loop (or part of a conditional chain).
+                    let parent = node.parent.take();
+                    node.parent.set(parent.clone());
+                    let mut expand_node = deep_clone(node, parent);
+                    render_context.borrow_mut().source_map.copy_lines(node, &expand_node);
+                    if let NodeData::Element { attrs, .. } = &expand_node.data {
+                        attrs.borrow_mut().retain(|attr| match &*attr.name.local {
+                            "x-for" | "x-each" | "x-as" | "x-index" | "x-else" | "x-else-if" => false,
+                            _ => true,
+                        });
+                    }
 
-                        let mut expand_node = deep_clone(node, parent);
-                        let mut should_append = true;
-                        let mut final_expansion_attrs: Vec<html5ever::interface::Attribute> = vec![];
-                        {
-                            use std::borrow::BorrowMut;
-                            {
-                                let mut attributes: (RefCell<Vec<html5ever::interface::Attribute>>, RefMut<Vec<html5ever::interface::Attribute>>) =
-                                match &expand_node.borrow_mut().data {
-                                    NodeData::Element { attrs, .. } => {
-                                        (attrs.clone(), attrs.borrow_mut())
-                                    },
-                                    _ => panic!("non element rendered in for")
-                                };
-
-                                for attr in attributes.1.iter_mut() {
-                                    trace!("{:?}", attr);
-                                    let name = &attr.name.local.to_string();
-                                    let script = String::from(&attr.value);
-                                    if name.starts_with(":") {
-                                        let final_name = name[1..].to_string();
-                                        attr.name = QualName::new(None, "".into(), final_name.clone().into());
-                                        let value: JSVal = eval(&global, &rt, cx, &script).unwrap();
-
-                                        // support for boolean flag type attribute names
-                                        if final_name == "checked" // checkbox
-                                            || final_name == "selected" // select box
-                                            || final_name == "disabled" // input
-                                            || final_name == "readonly" // input 
-                                            || final_name == "autoplay" // video
-                                            || final_name == "controls" // video
-                                            || final_name == "loop" // video 
-                                            || final_name == "muted" // video
-                                            || final_name == "open" // summary
-                                        {
-                                            // only if it's a boolean, perform boolification of it for the conditional
-                                            if boolify_jsvalue(cx, &value) {
-                                                attr.value = String::new().into();
-                                                final_expansion_attrs.push(attr.clone());
-                                            } else {
-                                                attr.value = String::new().into();
-                                                attr.name.local = String::new().into();
-                                            }
-                                        } else {
-                                            attr.value = stringify_jsvalue(cx, &value).into();
-                                            final_expansion_attrs.push(attr.clone());
-                                        }
-                                    } else if name == "x-if" {
-                                        let included = eval_in_engine_bool(&global, &rt, cx, &script);
-                                        if !included {
-                                            should_append = false;
-                                        }
-                                    } else if name == "x-each" && loop_expansion {
-                                        // do nothing
-                                    } else if name == "x-for" && loop_expansion {
-                                        // do nothing
-                                    } else if node_name == "slot" && name == "src" {
-                                        panic!("cannot set slot inside iter");
-                                    } else if name == "x-as" || name == "x-index" {
-                                        // Do nothing.
-                                    } else {
-                                        final_expansion_attrs.push(attr.clone());
-                                    }
-                                }
-                            }
-                        }
-                        if should_append {
-                            // substitute rendered attributes into the node.
-                            if let NodeData::Element { attrs, .. } = &expand_node.data {
-                                // info!("{:#?}", attrs);
-                                attrs.replace(final_expansion_attrs);
-                                // info!("{:#?}", attrs);
-                            }
-                            render_children(
-                                global,
-                                rt,
-                                cx,
-                                &mut expand_node,
-                                false,
-                                render_context.clone(),
-                                slot_contents.clone(),
-                                false,
-                                false
-                            );
-                            replacements.push(expand_node);
-                        }
+                    // 3. Render the copy in a scope with the loop's variables.
+                    push_scope(&global, &rt, cx, &params, &entry)?;
+                    let flags = render_children(
+                        global,
+                        rt,
+                        cx,
+                        &mut expand_node,
+                        false,
+                        render_context.clone(),
+                        slot_contents.clone(),
+                        false,
+                        false,
+                        options,
+                    );
+                    pop_scope(&global, &rt, cx)?;
+                    let flags = flags?;
 
-                        // Increment iteration counter.
-                        loop_index += 1;
+                    if !flags.remove {
+                        replacements.push(expand_node);
+                    }
+                    if let Some(replace) = flags.replace {
+                        replacements.extend(replace);
                     }
                 }
+
+                // a loop which renders nothing continues a conditional chain, so a following x-else renders instead.
+                if replacements.is_empty() {
+                    return Ok(CondGenFlags {
+                        conditional: empty_loop_state,
+                        remove: true,
+                        replace: None,
+                    });
+                }
+
+                needs_remove = true;
             }
 
             // attributes.push(html5ever::interface::Attribute {
@@ -1085,62 +1904,9 @@ unsafe fn render_children(
             //   value: "true".into()
             // });
 
-            if node_name != "script" && !needs_remove {
+            if node_name != "script" && !needs_remove && !has_content_children {
                 trace!("Rendering children...");
-                let mut out_children: Vec<Rc<Node>> = vec![];
-
-                {
-                    let mut children = node.children.borrow_mut();
-                    trace!("have {} children.", children.len());
-                    let mut iter_continue = false;
-                    let mut last_was_removed = false;
-
-                    for item in children.iter_mut() {
-                        trace!("Rendering child...");
-                        let flags = render_children(
-                            global,
-                            rt,
-                            cx,
-                            item,
-                            true,
-                            render_context.clone(),
-                            slot_contents.clone(),
-                            iter_continue,
-                            last_was_removed
-                        );
-
-                        // use the conditional flags to decide if we need to keep checking conditionals
-                        match flags.conditional {
-                            ConditionalChainState::NotConditional |
-                            ConditionalChainState::NoContinueChain => {
-                                iter_continue = false;
-                            },
-                            ConditionalChainState::CondinuedNotFound |
-                            ConditionalChainState::StartedNotFound => {
-                                iter_continue = true;
-                            }
-                        }
-
-                        // flag for if last was removed (used to trim unneeded whitespace text nodes surrounding removed elements)
-                        if flags.remove && flags.replace.is_none() {
-                            last_was_removed = true
-                        } else {
-                            last_was_removed = false;
-                        }
-                        
-                        // If not removed, add it to output
-                        if !flags.remove {
-                            out_children.push(item.clone())
-                        }
-
-                        // If there's replacements, add them.
-                        if let Some(replacements) = flags.replace {
-                            for item in replacements {
-                                out_children.push(item);
-                            }
-                        }
-                    }
-                }
+                let out_children = render_child_list(global, rt, cx, node, render_context.clone(), slot_contents.clone(), options)?;
 
                 debug!("-> leave element: {:?}", node_name);
                 trace!(
@@ -1151,11 +1917,11 @@ unsafe fn render_children(
                 node.children.replace(out_children);
             }
 
-            CondGenFlags {
+            Ok(CondGenFlags {
                 conditional: ConditionalChainState::NoContinueChain,
                 replace: Some(replacements),
                 remove: needs_remove,
-            }
+            })
         }
         NodeData::Text { contents } => {
             let mut tendril = contents.borrow_mut();
@@ -1167,41 +1933,60 @@ unsafe fn render_children(
             // If the last element was removed, and this is *all* whitespace, remove it from the tree.
             if last_was_removed && x.trim() == "" {
                 if conditional_chain_continue {
-                    return CondGenFlags {
+                    return Ok(CondGenFlags {
                         conditional: ConditionalChainState::CondinuedNotFound,
                         remove: true,
                         replace: None
-                    }
+                    })
                 }
-                return CondGenFlags::remove();
+                return Ok(CondGenFlags::remove());
             }
 
             let regex = Regex::new(r###"\{\{(.*?)\}\}"###).unwrap();
 
+            let mut failure: Option<DocgenError> = None;
             let result = regex.replace_all(&x, |caps: &Captures| {
                 if let Some(code) = caps.get(0) {
                     let string: &str = code.into();
                     trace!("render var: {}", string);
-                    return match eval_in_engine(&global, rt, cx, Some("variable_substitution"), string) {
-                        Ok(string) => string, 
-                        Err(string) => format!("error: {}", string) // TODO: check for debug / prod mode.
+                    let location = RefCell::borrow(&render_context).locate_in_text(node, &x, code.start());
+                    let rendered = eval_at(&global, rt, cx, string, location.as_ref()).and_then(|value| {
+                        try_stringify_jsvalue(&global, &rt, cx, &value).map_err(|err| err.at(location.as_ref()))
+                    });
+
+                    return match rendered {
+                        Ok(string) => string,
+                        Err(err) => {
+                            // in lenient mode, the expression renders nothing (the error is kept as a warning).
+                            match options.recover(Err(err), String::new()) {
+                                Ok(text) => text,
+                                Err(err) => {
+                                    failure = failure.take().or(Some(err));
+                                    String::new()
+                                }
+                            }
+                        }
                     };
                 }
 
                 return "".to_string();
             });
 
+            if let Some(err) = failure {
+                return Err(err);
+            }
+
             tendril.clear();
             tendril.try_push_bytes(result.as_bytes()).unwrap();
 
             if conditional_chain_continue {
-                return CondGenFlags {
+                return Ok(CondGenFlags {
                     conditional: ConditionalChainState::CondinuedNotFound,
                     remove: true,
                     replace: None
-                }
+                })
             }
-            return CondGenFlags::default();
+            return Ok(CondGenFlags::default());
         }
         NodeData::Comment { contents } => {
             let text = format!("{}", contents);
@@ -1209,7 +1994,10 @@ unsafe fn render_children(
             if trimmed.starts_with("slot:") {
                 debug!("got slot processing instruction: '{}'", text);
                 let x = trimmed.replace("slot:", "");
-                let slot_name = x.trim();
+                // `<!-- slot: name multiple -->` takes every contribution, like `<slot name="name" multiple>`.
+                let mut words = x.split_whitespace();
+                let slot_name = words.next().unwrap_or("");
+                let multiple = words.any(|word| word == "multiple");
 
                 if slot_name == "content" {
                     if let Some(contents) = slot_contents.clone().borrow() {
@@ -1218,26 +2006,26 @@ unsafe fn render_children(
                         let children: Ref<Vec<Rc<Node>>> = doc.children.borrow();
                         trace!("slot: got {} childen to doc", children.len());
                         if children.len() == 0 {
-                            return CondGenFlags {
+                            return Ok(CondGenFlags {
                                 conditional: ConditionalChainState::NotConditional,
                                 remove: true,
                                 replace: None,
-                            };
+                            });
                         }
                         let html: &Node = children[children.len() - 1].borrow();
                         let html_children = html.children.borrow();
                         trace!("slot: got {} childen to html", html_children.len());
                         if html_children.len() == 0 {
-                            return CondGenFlags {
+                            return Ok(CondGenFlags {
                                 conditional: ConditionalChainState::NotConditional,
                                 remove: true,
                                 replace: None,
-                            };
+                            });
                         }
                         let body: &Node = html_children[html_children.len() - 1].borrow();
                         let children = body.children.borrow();
                         // let items : &Vec<std::rc::Rc<html5ever::rcdom::Node>> = children.as_ref();
-                        return CondGenFlags {
+                        return Ok(CondGenFlags {
                             conditional: ConditionalChainState::NotConditional,
                             remove: true,
                             replace: Some(
@@ -1246,44 +2034,48 @@ unsafe fn render_children(
                                     .map(|cn| deep_clone(cn, Some(std::rc::Rc::downgrade(node))))
                                     .collect(),
                             ),
-                        };
+                        });
                     }
                 } else {
-                    let found_slot_contents =
-                        render_context.borrow_mut().find_slot_contents(&slot_name);
+                    let found_slot_contents = RefCell::borrow(&render_context).slot_nodes(slot_name, multiple);
 
                     if found_slot_contents.len() > 0 {
                         debug!("slot: found slot contents: {}", &slot_name);
-                        // let items : &Vec<std::rc::Rc<html5ever::rcdom::Node>> = children.as_ref();
-                        return CondGenFlags {
+                        return Ok(CondGenFlags {
                             conditional: ConditionalChainState::NotConditional,
                             remove: true,
-                            replace: Some(
-                                found_slot_contents
-                                    .iter()
-                                    .map(|v| v.borrow_mut().contents.clone())
-                                    .collect(),
-                            ),
-                        };
+                            replace: Some(found_slot_contents),
+                        });
                     } else {
-                        return CondGenFlags {
+                        return Ok(CondGenFlags {
                             conditional: ConditionalChainState::NotConditional,
                             remove: true,
                             replace: None,
-                        };
+                        });
                     }
                 }
             }
 
-            return CondGenFlags::default();
+            return Ok(CondGenFlags::default());
         }
-        NodeData::Doctype { .. } => return CondGenFlags::default(),
+        NodeData::Doctype { .. } => return Ok(CondGenFlags::default()),
         NodeData::ProcessingInstruction { .. } => {
-            return CondGenFlags::default();
+            return Ok(CondGenFlags::default());
         }
     }
 }
 
+/// How templates are parsed: like a browser would, but keeping their doctype.
+fn html_parse_opts() -> ParseOpts {
+    ParseOpts {
+        tree_builder: TreeBuilderOpts {
+            drop_doctype: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
 pub fn parse_and_render_dom(
     global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
     rt: &Runtime,
@@ -1292,24 +2084,36 @@ pub fn parse_and_render_dom(
     _variables: Option<Value>,
     parent_render_context: Option<Rc<RefCell<RenderContext>>>,
     slot_contents: Rc<Option<html5ever::rcdom::RcDom>>,
-) -> (html5ever::rcdom::RcDom, Rc<RefCell<RenderContext>>) {
-    let opts = ParseOpts {
-        tree_builder: TreeBuilderOpts {
-            drop_doctype: false,
-            ..Default::default()
-        },
-        ..Default::default()
-    };
+    path: Option<&std::path::Path>,
+    first_line: Option<usize>,
+    options: &RenderOptions,
+) -> Result<(html5ever::rcdom::RcDom, Rc<RefCell<RenderContext>>), DocgenError> {
+    let opts = html_parse_opts();
 
     // let dom2 = html5ever::parse_fragment(RcDom::default(), opts.clone(), QualName::new(None, "".into(), "div".into()), vec![])
     //     .from_utf8()
     //     .read_from(&mut template.as_bytes())
     //     .unwrap();
 
-    let dom = parse_document(RcDom::default(), opts)
-        .from_utf8()
-        .read_from(&mut template.as_bytes())
-        .unwrap();
+    // lines can only be traced back to templates which are parsed as they were written (markdown is searched in
+    // order instead, see `SourceMap::in_order`).
+    let (dom, source_map) = match first_line {
+        Some(first_line) => {
+            let mut source_map = source::SourceMap::new(template, first_line);
+            let sink = parse_document(source::LineTrackingSink::new(), opts)
+                .from_utf8()
+                .read_from(&mut template.as_bytes())
+                .map_err(|err| DocgenError::template(format!("failed to parse html: {}", err)).in_file(path))?;
+            (sink.into_source_map(&mut source_map), source_map)
+        }
+        None => {
+            let dom = parse_document(RcDom::default(), opts)
+                .from_utf8()
+                .read_from(&mut template.as_bytes())
+                .map_err(|err| DocgenError::template(format!("failed to parse html: {}", err)).in_file(path))?;
+            (dom, source::SourceMap::default())
+        }
+    };
 
     return render_dom(
         global,
@@ -1319,6 +2123,9 @@ pub fn parse_and_render_dom(
         _variables,
         parent_render_context,
         slot_contents,
+        path,
+        source_map,
+        options,
     );
 }
 
@@ -1331,7 +2138,10 @@ pub fn render_dom(
     _variables: Option<Value>,
     parent_render_context: Option<Rc<RefCell<RenderContext>>>,
     slot_contents: Rc<Option<html5ever::rcdom::RcDom>>,
-) -> (html5ever::rcdom::RcDom, Rc<RefCell<RenderContext>>) {
+    path: Option<&std::path::Path>,
+    source_map: source::SourceMap,
+    options: &RenderOptions,
+) -> Result<(html5ever::rcdom::RcDom, Rc<RefCell<RenderContext>>), DocgenError> {
     unsafe {
         // NOTE: this line is important, without it all JS_ calls seem to segfault.
 
@@ -1354,13 +2164,14 @@ pub fn render_dom(
                 env!("CARGO_PKG_VERSION"),
                 spidermonkey_version_str_slice
             ),
-        )
-        .unwrap();
+        )?;
 
         let render_context = Rc::new(RefCell::new(RenderContext::new_with_subcontext(
             dom.document.clone(),
             parent_render_context,
         )));
+        render_context.borrow_mut().path = path.map(std::path::Path::to_path_buf);
+        render_context.borrow_mut().source_map = source_map;
 
         {
             let document: &Node = dom.document.borrow();
@@ -1376,17 +2187,34 @@ pub fn render_dom(
                     slot_contents.clone(),
                     false,
                     false,
-                );
+                    options,
+                )
+                .map_err(|err| err.in_file(path))?;
             }
         }
 
-        (dom, render_context)
+        Ok((dom, render_context))
     }
 }
 
+#[cfg(not(test))]
+fn engine() -> Arc<JSEngine> {
+    JSEngine::init().unwrap()
+}
+
+#[cfg(test)]
+lazy_static! {
+    /// Spidermonkey can only be initialized once per process, so the tests share one engine.
+    static ref ENGINE: Arc<JSEngine> = JSEngine::init().unwrap();
+}
+
+#[cfg(test)]
+fn engine() -> Arc<JSEngine> {
+    ENGINE.clone()
+}
+
 pub fn init_runtime() -> (Runtime) {
-    let engine = JSEngine::init().unwrap();
-    Runtime::new(engine)
+    Runtime::new(engine())
 }
 
 pub fn init_js() -> (Runtime, *mut JSContext) {
@@ -1402,27 +2230,20 @@ pub fn render_injecting(
     template: &mut String,
     variables: Option<Value>,
     inject_dom: Rc<Option<html5ever::rcdom::RcDom>>,
-) -> String {
-    let (dom, render_context) =
-        parse_and_render_dom(&global, rt, cx, template, variables, None, inject_dom);
-
-    let mut buffer = vec![];
-
-    serialize(&mut buffer, &dom.document, Default::default())
-        .ok()
-        .expect("serialization failed");
+) -> Result<String, DocgenError> {
+    let (dom, _render_context) =
+        parse_and_render_dom(&global, rt, cx, template, variables, None, inject_dom, None, Some(1), &RenderOptions::default())?;
 
-    return String::from_utf8(buffer).unwrap();
+    serialize_dom(&dom)
 }
 
-pub fn serialize_dom(dom: &RcDom) -> String {
+pub fn serialize_dom(dom: &RcDom) -> Result<String, DocgenError> {
     let mut buffer = vec![];
 
     serialize(&mut buffer, &dom.document, Default::default())
-        .ok()
-        .expect("serialization failed");
+        .map_err(|err| DocgenError::template(format!("failed to serialize html: {}", err)))?;
 
-    return String::from_utf8(buffer).unwrap();
+    String::from_utf8(buffer).map_err(|err| DocgenError::template(format!("failed to serialize html: {}", err)))
 }
 
 pub fn render2(
@@ -1431,7 +2252,7 @@ pub fn render2(
     cx: *mut JSContext,
     template: &mut String,
     variables: Option<Value>,
-) -> String {
+) -> Result<String, DocgenError> {
     render_injecting(&global, rt, cx, template, variables, Rc::new(None))
 }
 
@@ -1440,9 +2261,10 @@ pub fn render_recursive(
     child_dom: Rc<Option<html5ever::rcdom::RcDom>>,
     child: Option<&mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>>,
     set_vars: Option<serde_json::Value>,
-) -> String {
+) -> Result<String, DocgenError> {
     let (rt, cx) = init_js();
-    render_recursive_path(&rt, cx, path, None, child_dom, child, set_vars)
+    render_recursive_path(&rt, cx, path, None, child_dom, child, set_vars, &RenderOptions::default())
+        .map(|rendered| rendered.html)
 }
 
 pub fn print_exception(rt: &Runtime, cx: *mut JSContext) {
@@ -1496,10 +2318,11 @@ pub fn render_recursive_path(
     child_dom: Rc<Option<html5ever::rcdom::RcDom>>,
     child: Option<&mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>>,
     set_vars: Option<serde_json::Value>,
-) -> String {
+    options: &RenderOptions,
+) -> Result<RenderedPage, DocgenError> {
     let path_str = format!("{}", path.display());
     debug!("rendering path: {}", path.display());
-    let mut template = std::fs::read_to_string(&path).unwrap();
+    let mut template = std::fs::read_to_string(&path).map_err(|err| DocgenError::io(path, err))?;
     let mut template_type = RenderType::Unknown;
 
     if path_str.ends_with(".html") || path_str.ends_with(".htm") {
@@ -1510,7 +2333,7 @@ pub fn render_recursive_path(
         template_type = RenderType::Markdown;
     }
 
-    render_recursive_string(rt, cx, &mut template, template_type, parent_render_context, child_dom, child, set_vars)
+    render_recursive_string(rt, cx, &mut template, template_type, Some(path), parent_render_context, child_dom, child, set_vars, options)
 }
 
 #[derive(Debug, PartialEq)]
@@ -1520,49 +2343,265 @@ pub enum RenderType {
     Unknown
 }
 
-/// Perform a recursive render.
-/// Attach parent global into jsengine if it exists
-pub fn render_recursive_string(
-    rt: &Runtime,
-    cx: *mut JSContext,
-    template: &mut String,
-    template_type: RenderType,
-    parent_render_context: Option<Rc<RefCell<RenderContext>>>,
-    child_dom: Rc<Option<html5ever::rcdom::RcDom>>,
-    child: Option<&mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>>,
-    set_vars: Option<serde_json::Value>,
-) -> String {
-    let mut template = template;
+/// Settings shared by every page (and layout) rendered in a build.
+pub struct RenderOptions {
+    /// Values loaded from the data directory, exposed to templates as `data`.
+    /// It's serialized to json once per build, and parsed by each page.
+    pub data: Option<String>,
 
-    unsafe {
-        rooted!(in(cx) let global =
-        JS_NewGlobalObject(cx, &SIMPLE_GLOBAL_CLASS, ptr::null_mut(),
-                                    OnNewGlobalHookOption::FireOnNewGlobalHook,
-                                    &CompartmentOptions::default())
-        );
+    /// Layout used by pages which don't set a `layout` variable. Not applied to layouts themselves.
+    /// Unlike a `layout` variable it's used as-is, rather than resolved against the page.
+    pub layout: Option<std::path::PathBuf>,
 
-        let _ac = mozjs::jsapi::JSAutoCompartment::new(cx, global.get());
-        assert!(mozjs::rust::wrappers::JS_InitStandardClasses(
-            cx,
-            global.handle()
-        ));
+    /// The site root, which paths starting with `/` are resolved against. Defaults to the working directory.
+    pub root: Option<std::path::PathBuf>,
 
-        let function = mozjs::rust::wrappers::JS_DefineFunction(
-            cx,
-            global.handle(),
-            b"console_log\0".as_ptr() as *const libc::c_char,
-            Some(console_log),
-            1,
-            0,
-        );
-        assert!(!function.is_null());
+    /// Directories (relative to the root) searched for includes which aren't found next to the including file.
+    pub include_dirs: Vec<String>,
 
-        let function2 = mozjs::rust::wrappers::JS_DefineFunction(
-            cx,
-            global.handle(),
-            b"console_error\0".as_ptr() as *const libc::c_char,
-            Some(console_error),
-            1,
+    /// Fail on any error in a template expression, instead of recording a warning and rendering around it.
+    pub strict: bool,
+
+    /// Errors recovered from while rendering, when not in strict mode.
+    pub warnings: RefCell<Vec<DocgenError>>,
+
+    /// Components which custom elements render, keyed by element name. See `components::load_components_dir`.
+    pub components: std::collections::HashMap<String, std::path::PathBuf>,
+
+    /// Files which were rendered as a layout, partial or component, so a build doesn't publish them as pages.
+    pub included: RefCell<std::collections::HashSet<std::path::PathBuf>>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            data: None,
+            layout: None,
+            root: None,
+            include_dirs: vec!["_layouts".to_string(), "_includes".to_string()],
+            strict: false,
+            warnings: RefCell::new(vec![]),
+            components: std::collections::HashMap::new(),
+            included: RefCell::new(std::collections::HashSet::new()),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Handle the result of evaluating a template expression.
+    /// In strict mode errors fail the render, otherwise they're recorded as warnings and `fallback` is used instead.
+    pub fn recover<T>(&self, result: Result<T, DocgenError>, fallback: T) -> Result<T, DocgenError> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(err) if self.strict => Err(err),
+            Err(err) => {
+                debug!("recovered from: {}", err);
+                self.warnings.borrow_mut().push(err);
+                Ok(fallback)
+            }
+        }
+    }
+
+    /// Record that a file was rendered as a layout, partial or component.
+    fn mark_included(&self, path: &std::path::Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.included.borrow_mut().insert(path);
+    }
+
+    /// Whether a file was rendered as a layout, partial or component of another page.
+    pub fn is_included(&self, path: &std::path::Path) -> bool {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.included.borrow().contains(&path)
+    }
+
+    /// Find the component a custom element renders, such as `<post-card>` for `post-card.html`.
+    /// The `x-` prefix is optional, so `<x-card>` renders `card.html` too.
+    pub fn find_component(&self, element: &str) -> Option<std::path::PathBuf> {
+        // custom elements always contain a hyphen, so standard elements can't be shadowed by a component.
+        if !element.contains('-') {
+            return None;
+        }
+
+        match self.components.get(element) {
+            Some(path) => Some(path.clone()),
+            None if element.starts_with("x-") => self.components.get(&element[2..]).cloned(),
+            None => None,
+        }
+    }
+
+    /// Resolve a path referenced by a template, in a `layout`, `<layout src>`, `<slot src>`, `<component src>` or `<script static src>`.
+    /// Paths starting with `/` are relative to the site root. Anything else is relative to the including file
+    /// (or the root, for templates which aren't files), falling back to the include directories.
+    pub fn resolve(&self, include: &str, from: Option<&std::path::Path>) -> std::path::PathBuf {
+        let root = match &self.root {
+            Some(root) => root.clone(),
+            None => std::path::PathBuf::from("."),
+        };
+
+        if include.starts_with('/') {
+            return root.join(include.trim_start_matches('/'));
+        }
+
+        let relative = match from.and_then(std::path::Path::parent) {
+            Some(dir) => dir.join(include),
+            None => root.join(include),
+        };
+
+        if !relative.exists() {
+            for dir in self.include_dirs.iter() {
+                let candidate = root.join(dir).join(include);
+                if candidate.exists() {
+                    return candidate;
+                }
+            }
+        }
+
+        relative
+    }
+}
+
+/// The output of a recursive render.
+pub struct RenderedPage {
+    /// The serialized page, wrapped in its layouts.
+    pub html: String,
+
+    /// The page's `permalink` variable, if it was set by front matter or a static script.
+    pub permalink: Option<String>,
+}
+
+/// Set a variable on a global object, converting it from a rust value.
+/// Like nested front matter keys, names containing a nul byte are skipped.
+unsafe fn set_global_value<T: EasyToJSVal>(
+    cx: *mut JSContext,
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    name: &str,
+    value: &T,
+) {
+    frontmatter::set_property(cx, global.get(), name, value);
+}
+
+/// Read a variable from a global object, if it is set to a string.
+unsafe fn get_string_property(
+    cx: *mut JSContext,
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    name: &str,
+) -> Option<String> {
+    let c_str = std::ffi::CString::new(name).unwrap();
+    let ptr = c_str.as_ptr() as *const i8;
+    rooted!(in(cx) let mut result = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, global.handle(), ptr, result.handle_mut());
+
+    if result.is_string() {
+        Some(stringify_jsvalue(cx, &result))
+    } else {
+        None
+    }
+}
+
+/// Perform a recursive render.
+/// Attach parent global into jsengine if it exists
+/// The layout a rendered template is wrapped in: its `layout` variable, or the default layout for a page. Only pages
+/// get the default layout (and not the layouts themselves), otherwise it would be wrapped around itself.
+unsafe fn resolve_layout(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    cx: *mut JSContext,
+    path: Option<&std::path::Path>,
+    is_page: bool,
+    options: &RenderOptions,
+) -> Option<std::path::PathBuf> {
+    let c_str = std::ffi::CString::new("layout").unwrap();
+    let ptr = c_str.as_ptr() as *const i8;
+    rooted!(in(cx) let mut layout_result = UndefinedValue());
+    mozjs::rust::wrappers::JS_GetProperty(cx, global.handle(), ptr, layout_result.handle_mut());
+    debug!("layout -> {}", stringify_jsvalue(cx, &layout_result));
+
+    if layout_result.is_string() {
+        Some(options.resolve(&stringify_jsvalue(cx, &layout_result), path))
+    } else if is_page {
+        options.layout.clone()
+    } else {
+        None
+    }
+}
+
+/// Finish rendering a template by wrapping it in its layout, if it has one, or serializing it as it is.
+unsafe fn render_in_layout(
+    global: &mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>,
+    rt: &Runtime,
+    cx: *mut JSContext,
+    partial: RcDom,
+    render_context: Rc<RefCell<RenderContext>>,
+    path: Option<&std::path::Path>,
+    is_page: bool,
+    set_vars: Option<serde_json::Value>,
+    options: &RenderOptions,
+) -> Result<RenderedPage, DocgenError> {
+    let permalink = get_string_property(cx, global, "permalink");
+
+    match resolve_layout(global, cx, path, is_page, options) {
+        Some(layout_path) => {
+            options.mark_included(&layout_path);
+            let mut rendered = render_recursive_path(
+                rt,
+                cx,
+                &layout_path,
+                Some(render_context),
+                Rc::new(Some(partial)),
+                Some(global),
+                set_vars,
+                options,
+            )?;
+            rendered.permalink = permalink;
+            Ok(rendered)
+        }
+        None => Ok(RenderedPage {
+            html: serialize_dom(&partial).map_err(|err| err.in_file(path))?,
+            permalink,
+        }),
+    }
+}
+
+pub fn render_recursive_string(
+    rt: &Runtime,
+    cx: *mut JSContext,
+    template: &mut String,
+    template_type: RenderType,
+    path: Option<&std::path::Path>,
+    parent_render_context: Option<Rc<RefCell<RenderContext>>>,
+    child_dom: Rc<Option<html5ever::rcdom::RcDom>>,
+    child: Option<&mozjs::rust::RootedGuard<'_, *mut mozjs::jsapi::JSObject>>,
+    set_vars: Option<serde_json::Value>,
+    options: &RenderOptions,
+) -> Result<RenderedPage, DocgenError> {
+    unsafe {
+        rooted!(in(cx) let global =
+        JS_NewGlobalObject(cx, &SIMPLE_GLOBAL_CLASS, ptr::null_mut(),
+                                    OnNewGlobalHookOption::FireOnNewGlobalHook,
+                                    &CompartmentOptions::default())
+        );
+
+        let _ac = mozjs::jsapi::JSAutoCompartment::new(cx, global.get());
+        assert!(mozjs::rust::wrappers::JS_InitStandardClasses(
+            cx,
+            global.handle()
+        ));
+
+        let function = mozjs::rust::wrappers::JS_DefineFunction(
+            cx,
+            global.handle(),
+            b"console_log\0".as_ptr() as *const libc::c_char,
+            Some(console_log),
+            1,
+            0,
+        );
+        assert!(!function.is_null());
+
+        let function2 = mozjs::rust::wrappers::JS_DefineFunction(
+            cx,
+            global.handle(),
+            b"console_error\0".as_ptr() as *const libc::c_char,
+            Some(console_error),
+            1,
             0,
         );
         assert!(!function2.is_null());
@@ -1610,8 +2649,7 @@ pub fn render_recursive_string(
             console.debug = console_debug;
             console.warn = console_warn;
             "###,
-        )
-        .unwrap();
+        )?;
 
         let result_name = std::ffi::CString::new("page").unwrap();
         let result_name_ptr = result_name.as_ptr() as *const i8;
@@ -1630,49 +2668,31 @@ pub fn render_recursive_string(
             );
         }
 
-        if let Some(set_vars) = &set_vars {
-            if let serde_json::Value::Object(map) = set_vars {
-                for (key, value) in map.iter() {
-                    // println!("Set value at {} to {:?}", key, value);
-                    rooted!(in(cx) let val = value.convert_to_jsval(cx));
-                    let fmname = std::ffi::CString::new(key.as_str()).unwrap();
-                    let fmname_ptr = fmname.as_ptr() as *const i8;
-                    mozjs::rust::wrappers::JS_SetProperty(
-                        cx,
-                        global.handle(),
-                        fmname_ptr,
-                        val.handle(),
-                    );
-                }
-            }
+        if let Some(data) = &options.data {
+            rooted!(in(cx) let mut json = UndefinedValue());
+            data.to_jsval(cx, json.handle_mut());
+            eval_with_value(&global, rt, cx, &json.get(), "var data = JSON.parse(__docgen.value);")
+                .map_err(|err| err.in_file(path))?;
         }
 
+        // front matter is read the same way for markdown and html pages.
         let mut override_contents: Option<String> = None;
-        if template_type == RenderType::Markdown {
+        if template_type == RenderType::Markdown || template_type == RenderType::Html {
             match frontmatter::infer_type(&template) {
                 frontmatter::MatterType::YAML => {
                     if let (Some(matter), read_contents) =
                         frontmatter::extract_frontmatter(&template)
                     {
                         override_contents = Some(read_contents.to_string());
-                        let val: serde_yaml::Value = serde_yaml::from_str(matter).unwrap();
+                        let val: serde_yaml::Value = serde_yaml::from_str(matter)
+                            .map_err(|err| DocgenError::front_matter(err).in_file(path))?;
 
                         if let serde_yaml::Value::Mapping(mapping) = val {
                             for (k, value_to_set) in mapping.iter() {
-                                if let serde_yaml::Value::String(string) = k {
-                                    debug!("Set value at {} to {:?}", string, value_to_set);
-                                    rooted!(in(cx) let val = value_to_set.convert_to_jsval(cx));
-                                    let fmname = std::ffi::CString::new(string.as_str()).unwrap();
-                                    let fmname_ptr = fmname.as_ptr() as *const i8;
-                                    mozjs::rust::wrappers::JS_SetProperty(
-                                        cx,
-                                        global.handle(),
-                                        fmname_ptr,
-                                        val.handle(),
-                                    );
-                                } else {
-                                    panic!("front matter keys must be strings.");
-                                }
+                                // keys which aren't strings are converted the same way as nested ones.
+                                let key = frontmatter::yaml_key(k);
+                                debug!("Set value at {} to {:?}", key, value_to_set);
+                                set_global_value(cx, &global, &key, value_to_set);
                             }
                         }
 
@@ -1680,24 +2700,86 @@ pub fn render_recursive_string(
                         // eval(&global, &rt, cx, "for (let i in frontmatter) { page[i] = frontmatter[i] }");
                     }
                 }
+                frontmatter::MatterType::JSON => {
+                    if let (Some(matter), read_contents) =
+                        frontmatter::extract_frontmatter(&template)
+                    {
+                        override_contents = Some(read_contents.to_string());
+                        let val: serde_json::Value = serde_json::from_str(matter)
+                            .map_err(|err| DocgenError::front_matter(err).in_file(path))?;
+
+                        if let serde_json::Value::Object(map) = val {
+                            for (key, value_to_set) in map.iter() {
+                                debug!("Set value at {} to {:?}", key, value_to_set);
+                                set_global_value(cx, &global, key, value_to_set);
+                            }
+                        } else {
+                            return Err(DocgenError::front_matter("json front matter must be an object").in_file(path));
+                        }
+                    }
+                }
+                frontmatter::MatterType::TOML => {
+                    if let (Some(matter), read_contents) =
+                        frontmatter::extract_frontmatter(&template)
+                    {
+                        override_contents = Some(read_contents.to_string());
+                        let val: cargo_toml::Value = matter
+                            .parse()
+                            .map_err(|err| DocgenError::front_matter(err).in_file(path))?;
+
+                        if let cargo_toml::Value::Table(table) = val {
+                            for (key, value_to_set) in table.iter() {
+                                debug!("Set value at {} to {:?}", key, value_to_set);
+                                set_global_value(cx, &global, key, value_to_set);
+                            }
+                        }
+                    }
+                }
                 _ => {}
             }
+        }
 
+        // variables from the command line are set after the front matter, so they override it.
+        if let Some(set_vars) = &set_vars {
+            if let serde_json::Value::Object(map) = set_vars {
+                for (key, value) in map.iter() {
+                    set_global_value(cx, &global, key, value);
+                }
+            }
+        }
 
-            let mut result = match override_contents {
-                Some(override_contents) => render::render_markdown(&override_contents),
-                None => render::render_markdown(template)
-            };
+        // front matter is stripped before parsing, so count its lines to report the right line numbers.
+        // (the contents are a suffix of the trimmed template.)
+        let first_line = match &override_contents {
+            Some(contents) => {
+                let trimmed = template.trim_end();
+                trimmed[..trimmed.len() - contents.len()].matches('\n').count() + 1
+            }
+            None => 1,
+        };
 
-            let (partial, child_render_context) = parse_and_render_dom(
+        if template_type == RenderType::Markdown {
+            let markdown = override_contents.unwrap_or_else(|| template.to_string());
+            let result = render::render_markdown(&markdown);
+
+            // the html doesn't keep the markdown's lines, so expressions are looked for in the markdown instead, in
+            // the order they're rendered.
+            let dom = parse_document(RcDom::default(), html_parse_opts())
+                .from_utf8()
+                .read_from(&mut result.as_bytes())
+                .map_err(|err| DocgenError::template(format!("failed to parse html: {}", err)).in_file(path))?;
+            let (partial, child_render_context) = render_dom(
                 &global,
                 &rt,
                 cx,
-                &mut result,
+                dom,
                 None,
                 parent_render_context,
                 child_dom,
-            );
+                path,
+                source::SourceMap::in_order(&markdown, first_line),
+                options,
+            )?;
 
             debug!("child render context info:");
             {
@@ -1711,77 +2793,54 @@ pub fn render_recursive_string(
                 );
             }
 
-            let c_str = std::ffi::CString::new("layout").unwrap();
-            let ptr = c_str.as_ptr() as *const i8;
-            rooted!(in(cx) let mut layout_result = UndefinedValue());
-            mozjs::rust::wrappers::JS_GetProperty(
+            return render_in_layout(
+                &global,
+                rt,
                 cx,
-                global.handle(),
-                ptr,
-                layout_result.handle_mut(),
+                partial,
+                child_render_context,
+                path,
+                child.is_none(),
+                set_vars,
+                options,
             );
-            debug!("layout -> {}", stringify_jsvalue(cx, &layout_result));
-
-            if layout_result.is_string() {
-                return render_recursive_path(
-                    &rt,
-                    cx,
-                    &std::path::Path::new(&stringify_jsvalue(cx, &layout_result)),
-                    Some(child_render_context),
-                    Rc::new(Some(partial)),
-                    Some(&global),
-                    set_vars,
-                );
-            } else {
-                return serialize_dom(&partial);
-            }
         } else if template_type == RenderType::Html {
             // let output = render(&global, &rt, cx, &mut contents, None);
             debug!("-> render partial html.");
+            let mut contents = override_contents.unwrap_or_else(|| template.to_string());
             let (partial, child_render_context) = parse_and_render_dom(
                 &global,
                 &rt,
                 cx,
-                &mut template,
+                &mut contents,
                 None,
                 parent_render_context,
                 child_dom,
-            );
+                path,
+                Some(first_line),
+                options,
+            )?;
             debug!("-> render partial html complete.");
-            let c_str = std::ffi::CString::new("layout").unwrap();
-            let ptr = c_str.as_ptr() as *const i8;
-            rooted!(in(cx) let mut layout_result = UndefinedValue());
-            mozjs::rust::wrappers::JS_GetProperty(
+            return render_in_layout(
+                &global,
+                rt,
                 cx,
-                global.handle(),
-                ptr,
-                layout_result.handle_mut(),
+                partial,
+                child_render_context,
+                path,
+                child.is_none(),
+                set_vars,
+                options,
             );
-            debug!("-> layout -> {}", stringify_jsvalue(cx, &layout_result));
-
-            if layout_result.is_string() {
-                debug!("-> render recursive!");
-                return render_recursive_path(
-                    &rt,
-                    cx,
-                    &std::path::Path::new(&stringify_jsvalue(cx, &layout_result)),
-                    Some(child_render_context),
-                    Rc::new(Some(partial)),
-                    Some(&global),
-                    set_vars,
-                );
-            } else {
-                return serialize_dom(&partial);
-            }
         } else {
-            panic!("no way to parse file.");
+            return Err(DocgenError::template("no way to parse file.".to_string()).in_file(path));
         }
     }
 }
 
 #[test]
 fn test_for_notation() {
-    let (name, kind, expr) = parse_for_notation("x in abc.foo()");
+    let (name, kind, expr) = parse_for_notation("x in abc.foo()").unwrap();
     assert_eq!(name, "x");
     assert_eq!(kind, "in");
     assert_eq!(expr, "abc.foo()");
@@ -1789,7 +2848,7 @@ fn test_for_notation() {
 
 #[test]
 fn test_for_of_notation() {
-    let (name, kind, expr) = parse_for_notation("x_val of [1,2,3]");
+    let (name, kind, expr) = parse_for_notation("x_val of [1,2,3]").unwrap();
     assert_eq!(name, "x_val");
     assert_eq!(kind, "of");
     assert_eq!(expr, "[1,2,3]");
@@ -1797,12 +2856,26 @@ fn test_for_of_notation() {
 
 #[test]
 fn test_each_object_values_notation() {
-    let (name, kind, expr) = parse_for_notation("item of Object.entries({ a: 1 })");
+    let (name, kind, expr) = parse_for_notation("item of Object.entries({ a: 1 })").unwrap();
     assert_eq!(name, "item");
     assert_eq!(kind, "of");
     assert_eq!(expr, "Object.entries({ a: 1 })");
 }
 
+#[test]
+fn test_for_destructuring_notation() {
+    let (name, kind, expr) = parse_for_notation("(item, index) in items").unwrap();
+    assert_eq!((name.as_str(), kind.as_str(), expr.as_str()), ("(item, index)", "in", "items"));
+
+    let (name, _, expr) = parse_for_notation("[k, v] of Object.entries(obj)").unwrap();
+    assert_eq!((name.as_str(), expr.as_str()), ("[k, v]", "Object.entries(obj)"));
+
+    let (name, _, _) = parse_for_notation("{title, url} of links").unwrap();
+    assert_eq!(name, "{title, url}");
+
+    assert!(parse_for_notation("item from items").is_err());
+}
+
 #[test]
 fn test_render_html() {
 
@@ -1839,6 +2912,640 @@ let wanted = r###"<!DOCTYPE html><html><head></head>
 </body></html>"###;
 
     let (rt, cx) = init_js();
-    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, std::rc::Rc::new(None), None, None);
-    assert_eq!(rendered, wanted);
-}
\ No newline at end of file
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_html_front_matter() {
+    let mut torender = r###"---
+title: Front Matter
+permalink: /front-matter/
+---
+<!doctype html>
+<html><head><title>{{title}}</title></head><body></body></html>"###.to_string();
+
+    let wanted = r###"<!DOCTYPE html><html><head><title>Front Matter</title></head><body></body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+    assert_eq!(rendered.permalink, Some("/front-matter/".to_string()));
+}
+
+#[test]
+fn test_render_json_front_matter() {
+    let mut torender = r###"---
+{ "title": "Front Matter", "author": { "name": "Rich" }, "tags": ["docgen", "json"] }
+---
+<p>{{page.title}} by {{author.name}}, {{tags.length}} tags</p>"###.to_string();
+
+    let wanted = r###"<html><head></head><body><p>Front Matter by Rich, 2 tags</p></body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_front_matter_keys() {
+    // top level keys which aren't strings are converted like nested ones, and names with a nul byte are skipped.
+    let mut torender = "---\n1: one\ntrue: yep\n\"a\\0b\": skipped\nnested:\n  2: two\n---\n<p>{{page[1]}} {{page.true}} {{nested[2]}}</p>".to_string();
+    let wanted = r###"<html><head></head><body><p>one yep two</p></body></html>"###;
+
+    let vars = serde_json::json!({ "c\u{0}d": "skipped" });
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, Some(vars), &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_vars_override_front_matter() {
+    let mut torender = r###"---
+title: Front Matter
+draft: true
+---
+<p>{{title}} {{draft}}</p>"###.to_string();
+
+    let wanted = r###"<html><head></head><body><p>Command Line true</p></body></html>"###;
+
+    let vars = serde_json::json!({ "title": "Command Line" });
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, Some(vars), &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_data() {
+    let mut torender = r###"<p x-for="link in data.navigation">{{link.title}}</p>"###.to_string();
+    let wanted = r###"<html><head></head><body><p>Home</p><p>About</p></body></html>"###;
+
+    let mut options = RenderOptions::default();
+    options.data = Some(r###"{ "navigation": [{ "title": "Home" }, { "title": "About" }] }"###.to_string());
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_data_in_component() {
+    let dir = testing::TempDir::new("component_data");
+    dir.write("card.html", r###"<i>{{data.site.title}}</i>"###);
+
+    let mut torender = r###"<component src="/card.html"></component>"###.to_string();
+    let wanted = r###"<html><head></head><body><i>Docgen</i></body></html>"###;
+
+    let mut options = RenderOptions::default();
+    options.root = Some(dir.path().to_path_buf());
+    options.data = Some(r###"{ "site": { "title": "Docgen" } }"###.to_string());
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_resolve_include() {
+    let dir = testing::TempDir::new("resolve");
+    dir.write("_layouts/post.html", "");
+    dir.write("posts/footer.html", "");
+    let root = dir.path().to_path_buf();
+
+    let mut options = RenderOptions::default();
+    options.root = Some(root.clone());
+    let page = root.join("posts/page.html");
+
+    let footer = options.resolve("footer.html", Some(page.as_path()));
+    let layout = options.resolve("post.html", Some(page.as_path()));
+    let absolute = options.resolve("/posts/footer.html", Some(page.as_path()));
+    let missing = options.resolve("missing.html", Some(page.as_path()));
+    let without_file = options.resolve("posts/footer.html", None);
+
+    assert_eq!(footer, root.join("posts/footer.html"));
+    assert_eq!(layout, root.join("_layouts/post.html"));
+    assert_eq!(absolute, root.join("posts/footer.html"));
+    assert_eq!(missing, root.join("posts/missing.html"));
+    assert_eq!(without_file, root.join("posts/footer.html"));
+}
+
+#[test]
+fn test_render_component() {
+    let dir = testing::TempDir::new("component");
+    dir.write(
+        "card.html",
+        r###"<div class="card"><h2><slot name="title"></slot></h2><p>{{props.subtitle}} {{props.postCount}}</p><slot></slot></div>"###,
+    );
+
+    let mut torender = r###"<html><body>
+<script static>let posts = [{ title: "a" }, { title: "b" }];</script>
+<component x-for="post of posts" src="/card.html" :post-count="posts.length" subtitle="by rich"><b slot="title">{{post.title}}</b><i>{{post.title}}!</i></component>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<div class="card"><h2><b>a</b></h2><p>by rich 2</p><i>a!</i></div><div class="card"><h2><b>b</b></h2><p>by rich 2</p><i>b!</i></div>
+</body></html>"###;
+
+    let mut options = RenderOptions::default();
+    options.root = Some(dir.path().to_path_buf());
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options);
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_component_without_src() {
+    let template = r###"<p>Before</p>
+<component title="Example"><b>Contents</b></component>"###;
+
+    let mut torender = template.to_string();
+    let (rt, cx) = init_js();
+    let mut options = RenderOptions::default();
+    options.strict = true;
+    match render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options) {
+        Err(err) => {
+            assert_eq!(err.line, Some(2));
+            assert!(err.to_string().contains("<component> requires a src attribute"));
+        }
+        _ => panic!("expected a template error"),
+    }
+
+    let mut torender = template.to_string();
+    let options = RenderOptions::default();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options).unwrap();
+    assert_eq!(rendered.html, "<html><head></head><body><p>Before</p>\n</body></html>");
+    assert_eq!(options.warnings.borrow().len(), 1);
+}
+
+#[test]
+fn test_render_registered_components() {
+    let dir = testing::TempDir::new("registry");
+    dir.write("_components/post-card.html", r###"<b>{{props.title}}</b><slot></slot>"###);
+    dir.write("_components/card.html", r###"<i>{{props.src}}</i>"###);
+
+    let mut torender = r###"<html><body><post-card :title="'Hello'">!</post-card><x-card src="a.png"></x-card><other-card></other-card></body></html>"###.to_string();
+    let wanted = r###"<html><head></head><body><b>Hello</b>!<i>a.png</i><other-card></other-card></body></html>"###;
+
+    let mut options = RenderOptions::default();
+    options.components = components::load_components_dir(&dir.path().join("_components")).unwrap();
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options);
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_slot_fallback() {
+    let mut torender = r###"<html><body><script static>let name = "docgen";</script><b slot="title">Hi {{name}}</b><h1><slot name="title">Untitled</slot></h1><aside><slot name="sidebar"><p>Default {{name}}</p></slot></aside><main><slot name="content">No content</slot></main></body></html>"###.to_string();
+    let wanted = r###"<html><head></head><body><h1><b>Hi docgen</b></h1><aside><p>Default docgen</p></aside><main>No content</main></body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_layout_element() {
+    let dir = testing::TempDir::new("layout");
+    dir.write(
+        "base.html",
+        r###"<html><body><h1><slot name="title">Untitled</slot></h1><slot name="content"></slot><slot name="javascript" multiple></slot></body></html>"###,
+    );
+    dir.write(
+        "post.html",
+        r###"<layout src="./base.html"><b slot="title">Post</b><script slot="javascript" src="/post.js"></script></layout><article><slot name="content"></slot></article>"###,
+    );
+
+    // the page's title is the innermost, so it wins over the post layout's. Both scripts fill the multiple slot.
+    let mut torender = r###"<layout src="./post.html"><b slot="title">Page</b><script slot="javascript" src="/page.js"></script></layout><p>Hello</p>"###.to_string();
+    let wanted = r###"<html><head></head><body><h1><b>Page</b></h1><article><p>Hello</p></article><script src="/post.js"></script><script src="/page.js"></script></body></html>"###;
+
+    let page = dir.path().join("page.html");
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(page.as_path()), None, std::rc::Rc::new(None), None, None, &RenderOptions::default());
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_slot_conditionals() {
+    // contributions take part in x-if/x-else chains where they're written.
+    let mut torender = r###"<html><body><b slot="title" x-if="false">A</b><b slot="title" x-else>B</b><i slot="note" x-if="true">C</i><i x-else>D</i><h1><slot name="title"></slot></h1><p><slot name="note"></slot></p></body></html>"###.to_string();
+    let wanted = r###"<html><head></head><body><h1><b>B</b></h1><p><i>C</i></p></body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default());
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_layout_in_component_error() {
+    let dir = testing::TempDir::new("component_layout");
+    let card = dir.write("card.html", r###"<layout src="./base.html"></layout><b>Card</b>"###);
+
+    let mut torender = r###"<html><body><component src="/card.html"></component></body></html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.root = Some(dir.path().to_path_buf());
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.file, Some(card));
+            assert!(format!("{}", err.cause).contains("only pages can have a layout"));
+        }
+        _ => panic!("expected a template error"),
+    }
+}
+
+#[test]
+fn test_render_slot_single_contribution() {
+    // a slot without `multiple` takes one contribution, even when a context makes several. A looped element is
+    // a single contribution.
+    let mut torender = r###"<html><body><b slot="title">First</b><b slot="title">Second</b><i slot="tags" x-for="tag of ['a', 'b']">{{tag}}</i><i slot="tags">c</i><h1><slot name="title"></slot></h1><p><slot name="tags"></slot></p></body></html>"###.to_string();
+    let wanted = r###"<html><head></head><body><h1><b>First</b></h1><p><i>a</i><i>b</i></p></body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default());
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_slot_comments() {
+    let dir = testing::TempDir::new("slot_comments");
+    dir.write(
+        "base.html",
+        r###"<html><body><h1><!-- slot: title --></h1><!-- slot: content --><!-- slot: javascript multiple --></body></html>"###,
+    );
+    dir.write(
+        "post.html",
+        r###"<layout src="./base.html"><b slot="title">Post</b><script slot="javascript" src="/post.js"></script></layout><article><!-- slot: content --></article>"###,
+    );
+
+    let mut torender = r###"<layout src="./post.html"><b slot="title">Page</b><script slot="javascript" src="/page.js"></script></layout><p>Hello</p>"###.to_string();
+    let wanted = r###"<html><head></head><body><h1><b>Page</b></h1><article><p>Hello</p></article><script src="/post.js"></script><script src="/page.js"></script></body></html>"###;
+
+    let page = dir.path().join("page.html");
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(page.as_path()), None, std::rc::Rc::new(None), None, None, &RenderOptions::default());
+    assert_eq!(rendered.unwrap().html, wanted);
+}
+
+#[test]
+fn test_render_missing_partial_error() {
+    let mut torender = r###"<html><body><slot src="/docgen-missing-partial.html"></slot></body></html>"###.to_string();
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default());
+    match result {
+        Err(DocgenError { cause: ErrorCause::Io(path, _), .. }) => {
+            assert!(path.ends_with("docgen-missing-partial.html"))
+        }
+        _ => panic!("expected a missing file error"),
+    }
+}
+
+#[test]
+fn test_render_script_error() {
+    let mut torender = r###"<html><body><div x-for="item in not_defined">{{item}}</div></body></html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(std::path::Path::new("index.html")), None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.file, Some(std::path::PathBuf::from("index.html")));
+            match err.cause {
+                ErrorCause::Script(message) => assert!(message.contains("not_defined")),
+                _ => panic!("expected a script error"),
+            }
+        }
+        _ => panic!("expected a script error"),
+    }
+}
+
+#[test]
+fn test_render_error_location() {
+    let mut torender = r###"---
+title: Locations
+---
+<html>
+<body>
+  <a :href="missing.url">{{title}}</a>
+</body>
+</html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(std::path::Path::new("index.html")), None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.line, Some(6));
+            assert_eq!(err.column, Some(13));
+            assert_eq!(err.source, Some("  <a :href=\"missing.url\">{{title}}</a>".to_string()));
+        }
+        _ => panic!("expected a script error"),
+    }
+}
+
+#[test]
+fn test_render_repeated_expression_locations() {
+    let mut torender = r###"<html><body>
+<p>{{missing.a}} {{missing.a}}</p>
+</body></html>"###.to_string();
+
+    let options = RenderOptions::default();
+
+    let (rt, cx) = init_js();
+    render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(std::path::Path::new("index.html")), None, std::rc::Rc::new(None), None, None, &options).unwrap();
+    let warnings = options.warnings.borrow();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].line, Some(2));
+    assert_eq!(warnings[0].column, Some(4));
+    assert_eq!(warnings[1].line, Some(2));
+    assert_eq!(warnings[1].column, Some(18));
+}
+
+#[test]
+fn test_render_markdown_error_location() {
+    let mut torender = r###"---
+title: Markdown
+---
+# {{title}}
+
+Some text with {{missing.value}}."###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Markdown, Some(std::path::Path::new("index.md")), None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.file, Some(std::path::PathBuf::from("index.md")));
+            assert_eq!(err.line, Some(6));
+            assert_eq!(err.column, Some(16));
+            assert_eq!(err.source, Some("Some text with {{missing.value}}.".to_string()));
+        }
+        _ => panic!("expected a script error"),
+    }
+}
+
+#[test]
+fn test_render_error_location_in_loop() {
+    let mut torender = r###"<html><body>
+<ul>
+  <li x-for="item of [1, 2]">{{item.missing.value}}</li>
+</ul>
+</body></html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(std::path::Path::new("index.html")), None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.line, Some(3));
+            assert_eq!(err.column, Some(30));
+            assert_eq!(err.source, Some("  <li x-for=\"item of [1, 2]\">{{item.missing.value}}</li>".to_string()));
+        }
+        _ => panic!("expected a script error"),
+    }
+}
+
+#[test]
+fn test_render_static_script_error_location() {
+    let mut torender = r###"<html><body>
+<div x-for="item of [1]">
+  <script static>
+    var double = item * 2;
+    item.missing.value;
+  </script>
+</div>
+</body></html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, Some(std::path::Path::new("index.html")), None, std::rc::Rc::new(None), None, None, &options);
+    match result {
+        Err(err) => {
+            assert_eq!(err.file, Some(std::path::PathBuf::from("index.html")));
+            assert_eq!(err.line, Some(5));
+            assert_eq!(err.source, Some("    item.missing.value;".to_string()));
+        }
+        _ => panic!("expected a script error"),
+    }
+}
+
+#[test]
+fn test_render_lenient_warnings() {
+    let mut torender = r###"<html><body><a :href="missing.url">{{missing.title}}</a><p x-if="missing.visible">Hidden</p></body></html>"###.to_string();
+    let wanted = r###"<html><head></head><body><a></a></body></html>"###;
+
+    let options = RenderOptions::default();
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options).unwrap();
+    assert_eq!(rendered.html, wanted);
+    assert_eq!(options.warnings.borrow().len(), 3);
+}
+
+#[test]
+fn test_render_objects() {
+    let mut torender = r###"<html><body>
+<script static>
+let tags = ["rust", "html"];
+let post = { title: "Hello", date: new Date(Date.UTC(2019, 2, 1)) };
+let author = { toString: function () { return "Rich"; } };
+</script>
+<p :data-tags="tags">{{tags}}</p>
+<p>{{post.date}}</p>
+<p>{{author}}</p>
+<p>{{Symbol("docgen")}}</p>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p data-tags="[&quot;rust&quot;,&quot;html&quot;]">["rust","html"]</p>
+<p>2019-03-01T00:00:00.000Z</p>
+<p>Rich</p>
+<p>Symbol(docgen)</p>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_raw_html() {
+    let mut torender = r###"<html><body>
+<script static>let excerpt = "<em>Hello</em>, {{ world }}";</script>
+<p>{{excerpt}}</p>
+<div x-html="excerpt"><span>placeholder</span></div>
+<div x-html="undefined"></div>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p>&lt;em&gt;Hello&lt;/em&gt;, {{ world }}</p>
+<div><em>Hello</em>, {{ world }}</div>
+<div></div>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_text_and_markdown() {
+    let mut torender = r###"<html><body>
+<script static>let post = { description: "Fish & <chips>", summary: "Some *markdown*" };</script>
+<p x-text="post.description">placeholder</p>
+<div x-markdown="post.summary"></div>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p>Fish &amp; &lt;chips&gt;</p>
+<div><p>Some <em>markdown</em></p>
+</div>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_class_and_style() {
+    let mut torender = r###"<html><body>
+<script static>let active = true; let tags = ["a", "b"]; let color = "red";</script>
+<p class="item" :class="{ active: active, hidden: !active }" style="margin: 0;" :style="{ color: color, fontSize: '2em' }"></p>
+<p :class="['first', { second: false }, tags]"></p>
+<p :class="null" :style="'display: none;'"></p>
+<p x-for="tag of tags" class="tag" :class="'tag-' + tag"></p>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p class="item active" style="margin: 0; color: red; font-size: 2em"></p>
+<p class="first a b"></p>
+<p style="display: none"></p>
+<p class="tag tag-a"></p><p class="tag tag-b"></p>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_loop_scopes() {
+    let mut torender = r###"<html><body>
+<script static>let item = "global"; let rows = [[1, 2], [3]];</script>
+<p x-for="item of rows"><span x-for="item of item">{{item}}</span>;</p>
+<p>{{item}} {{typeof i}}</p>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p><span>1</span><span>2</span>;</p><p><span>3</span>;</p>
+<p>global undefined</p>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_static_script_in_loop() {
+    let mut torender = r###"<html><body>
+<script static>var posts = [{ title: "First" }, { title: "Second" }];</script>
+<p x-for="post of posts"><script static>var heading = post.title.toUpperCase() + " " + i;</script>{{heading}}</p>
+<p>{{typeof heading}} {{Object.keys(this).filter(function (key) { return key.indexOf("__docgen") === 0; }).length}}</p>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p>FIRST 0</p><p>SECOND 1</p>
+<p>undefined 0</p>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_loop_notations() {
+    let mut torender = r###"<html><body>
+<script static>let links = [{ title: "a", url: "/a" }, { title: "b", url: "/b" }]; let obj = { x: 1, y: 2 };</script>
+<p><b x-for="(link, index) in links">{{index}}:{{link.title}}</b></p>
+<p><b x-for="(value, key, index) in obj">{{index}}:{{key}}={{value}}</b></p>
+<p><b x-for="key in obj">{{key}}</b><b x-for="value of obj">{{value}}</b></p>
+<p><b x-for="[k, v] of Object.entries(obj)">{{k}}{{v}}</b></p>
+<p><a x-for="{title, url} of links" :href="url">{{title}}</a></p>
+<p><b x-for="n in 3">{{n}}</b></p>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<p><b>0:a</b><b>1:b</b></p>
+<p><b>0:x=1</b><b>1:y=2</b></p>
+<p><b>x</b><b>y</b><b>1</b><b>2</b></p>
+<p><b>x1</b><b>y2</b></p>
+<p><a href="/a">a</a><a href="/b">b</a></p>
+<p><b>1</b><b>2</b><b>3</b></p>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_loop_conditionals() {
+    let mut torender = r###"<html><body>
+<script static>let posts = []; let drafts = [{ title: "a", draft: true }, { title: "b", draft: false }]; let ready = false;</script>
+<ul>
+<li x-for="post of posts">{{post.title}}</li>
+<li x-else>No posts yet</li>
+</ul>
+<ul>
+<li x-for="post of drafts" x-if="!post.draft">{{post.title}}</li>
+<li x-else>No posts yet</li>
+</ul>
+<ul>
+<li x-if="ready">Loading</li>
+<li x-else-if="drafts.length" x-for="post of drafts">{{post.title}}</li>
+<li x-else>Nothing</li>
+</ul>
+</body></html>"###.to_string();
+
+    let wanted = r###"<html><head></head><body>
+<ul>
+<li>No posts yet</li>
+</ul>
+<ul>
+<li>b</li>
+</ul>
+<ul>
+<li>a</li><li>b</li>
+</ul>
+</body></html>"###;
+
+    let (rt, cx) = init_js();
+    let rendered = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &RenderOptions::default()).unwrap();
+    assert_eq!(rendered.html, wanted);
+}
+
+#[test]
+fn test_render_function_error() {
+    let mut torender = r###"<html><body><script static>let render = function () {};</script><p>{{render}}</p></body></html>"###.to_string();
+
+    let mut options = RenderOptions::default();
+    options.strict = true;
+
+    let (rt, cx) = init_js();
+    let result = render_recursive_string(&rt, cx, &mut torender, RenderType::Html, None, None, std::rc::Rc::new(None), None, None, &options);
+    assert!(result.is_err());
+}
@@ -1,6 +1,6 @@
 use std::io::{self, Read};
 extern crate docgen;
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use glob::glob;
 mod render;
 
@@ -13,6 +13,218 @@ use log::LevelFilter;
 use std::env;
 use std::io::Write;
 
+/// Flags for passing variables into a render. They're global, so they can be given before or after the command.
+fn var_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("var")
+            .long("var")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("KEY=VALUE")
+            .global(true)
+            .help("Set a variable. Values are parsed as json if possible, dotted keys set nested values"),
+        Arg::with_name("vars")
+            .long("vars")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("FILE")
+            .global(true)
+            .help("Set variables from a json or yaml file"),
+    ]
+}
+
+/// Flag for failing on template errors, shared by every command.
+fn strict_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("strict")
+        .long("strict")
+        .help("Fail on any error in a template expression, instead of warning and rendering around it")
+}
+
+/// Print the errors a render recovered from.
+fn print_warnings(warnings: &[docgen::DocgenError]) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    warn!("{} template errors were rendered around (use --strict to fail on them):", warnings.len());
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+}
+
+/// Collect `--var` and `--vars` flags, merged in the order they were given.
+fn collect_vars(matches: &ArgMatches) -> io::Result<serde_json::Value> {
+    let mut flags: Vec<(usize, &str, &str)> = vec![];
+    for name in ["var", "vars"].iter() {
+        if let (Some(indices), Some(values)) = (matches.indices_of(name), matches.values_of(name)) {
+            flags.extend(indices.zip(values).map(|(index, value)| (index, *name, value)));
+        }
+    }
+    flags.sort_by_key(|(index, _, _)| *index);
+
+    let mut vars = serde_json::Value::Object(serde_json::Map::new());
+    for (_, name, value) in flags {
+        let value = if name == "var" {
+            docgen::vars::parse_var(value)
+        } else {
+            docgen::vars::load_vars_file(std::path::Path::new(value))
+        }
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        docgen::vars::merge_vars(&mut vars, value);
+    }
+
+    Ok(vars)
+}
+
+/// Help text for the build flags, mentioning the same defaults the configuration uses.
+struct BuildHelp {
+    out: String,
+    permalink: String,
+    data: String,
+    components: String,
+}
+
+impl BuildHelp {
+    fn new() -> BuildHelp {
+        BuildHelp {
+            out: format!(
+                "Output directory, relative to the working directory [default: {} in the site root]",
+                docgen::config::DEFAULT_OUTPUT
+            ),
+            permalink: format!(
+                "Permalink pattern for dated pages without a permalink [default: {}]",
+                docgen::site::DEFAULT_PERMALINK
+            ),
+            data: format!(
+                "Data directory, relative to the source directory [default: {}]",
+                docgen::config::DEFAULT_DATA
+            ),
+            components: format!(
+                "Components directory, relative to the source directory [default: {}]",
+                docgen::config::DEFAULT_COMPONENTS
+            ),
+        }
+    }
+}
+
+/// The command line interface.
+fn app(help: &BuildHelp) -> App<'_, '_> {
+    App::new("docgen")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(
+            Arg::with_name("input")
+                .short("i")
+                .default_value("./**/{*.html,*.htm,*.md}")
+                .help("Input File Glob Expression"),
+        )
+        .args(&var_args())
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("Render every page in a site into an output directory")
+                .arg(
+                    Arg::with_name("root")
+                        .index(1)
+                        .default_value(".")
+                        .help("Site root directory, containing docgen.yaml or docgen.toml"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .takes_value(true)
+                        .help("Configuration file, instead of the one in the site root"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .short("o")
+                        .long("out")
+                        .takes_value(true)
+                        .help(&help.out),
+                )
+                .arg(
+                    Arg::with_name("permalink")
+                        .long("permalink")
+                        .takes_value(true)
+                        .help(&help.permalink),
+                )
+                .arg(
+                    Arg::with_name("data")
+                        .long("data")
+                        .takes_value(true)
+                        .help(&help.data),
+                )
+                .arg(
+                    Arg::with_name("components")
+                        .long("components")
+                        .takes_value(true)
+                        .help(&help.components),
+                )
+                .arg(strict_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Render a single template to stdout")
+                .arg(
+                    Arg::with_name("file")
+                        .index(1)
+                        .required_unless("stdin")
+                        .conflicts_with("stdin")
+                        .help("Template to render"),
+                )
+                .arg(
+                    Arg::with_name("stdin")
+                        .long("stdin")
+                        .help("Read the template from stdin"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .short("t")
+                        .long("type")
+                        .takes_value(true)
+                        .possible_values(&["html", "md"])
+                        .help("Template type [default: from the file extension, html for stdin]"),
+                )
+                .arg(
+                    Arg::with_name("base")
+                        .short("b")
+                        .long("base")
+                        .takes_value(true)
+                        .help("Site root, which includes starting with / are resolved from [default: .]"),
+                )
+                .arg(strict_arg()),
+        )
+}
+
+/// Options for the render command. The data and components directories are loaded from the site root like a build
+/// does, so templates can use them the same way.
+fn render_options(matches: &ArgMatches) -> io::Result<docgen::RenderOptions> {
+    let mut options = docgen::RenderOptions::default();
+    options.root = matches.value_of("base").map(std::path::PathBuf::from);
+    options.strict = matches.is_present("strict");
+
+    let root = options.root.clone().unwrap_or_default();
+    let config = docgen::config::Config::find(&root).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let data = root.join(&config.data);
+    if data.is_dir() {
+        let data = docgen::data::load_data_dir(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        options.data = Some(data.to_json().to_string());
+    }
+
+    let components = root.join(&config.components);
+    if components.is_dir() {
+        options.components = docgen::components::load_components_dir(&components)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    }
+
+    Ok(options)
+}
+
 fn main() -> io::Result<()> {
     let mut builder = Builder::from_default_env();
 
@@ -34,40 +246,167 @@ fn main() -> io::Result<()> {
         .filter(None, LevelFilter::Info)
         .init();
 
-    let matches = App::new("docgen")
-        .version(env!("CARGO_PKG_VERSION"))
-        .author(env!("CARGO_PKG_AUTHORS"))
-        .about(env!("CARGO_PKG_DESCRIPTION"))
-        .arg(
-            Arg::with_name("input")
-                .short("i")
-                .default_value("./**/{*.html,*.htm,*.md}")
-                .help("Input File Glob Expression"),
-        )
-        .get_matches();
+    let help = BuildHelp::new();
+    let matches = app(&help).get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("build") {
+        let root = std::path::PathBuf::from(matches.value_of("root").unwrap());
+        let config = match matches.value_of("config") {
+            Some(path) => docgen::config::Config::load(std::path::Path::new(path)),
+            None => docgen::config::Config::find(&root),
+        }
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
+        // command line flags take precedence over the configuration file. Unlike the paths in the configuration
+        // file, --out is relative to the working directory, like any other path given on the command line.
+        let mut options = docgen::site::BuildOptions::from_config(&root, config);
+        if let Some(out) = matches.value_of("out") {
+            options.output = std::path::PathBuf::from(out);
+        }
+        if let Some(permalink) = matches.value_of("permalink") {
+            options.permalink = permalink.to_string();
+        }
+        if let Some(data) = matches.value_of("data") {
+            options.data = options.source.join(data);
+        }
+        if let Some(components) = matches.value_of("components") {
+            options.components = options.source.join(components);
+        }
+        if let serde_json::Value::Object(vars) = collect_vars(matches)? {
+            options.vars = vars;
+        }
+        options.strict = matches.is_present("strict");
+
+        let report = docgen::site::build_site(&options)?;
+        info!(
+            "built {} pages, copied {} files, {} failed.",
+            report.rendered.len(),
+            report.copied,
+            report.failed.len()
+        );
+        print_warnings(&report.warnings);
+
+        // each failed page was already reported as it was built.
+        if !report.failed.is_empty() {
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("render") {
+        let vars = collect_vars(matches)?;
+
+        let path = matches.value_of("file").map(std::path::Path::new);
+        let (mut template, extension) = match path {
+            Some(path) => {
+                let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+                (std::fs::read_to_string(path)?, extension.to_string())
+            }
+            None => {
+                let mut buffer = String::new();
+                io::stdin().read_to_string(&mut buffer)?;
+                (buffer, "html".to_string())
+            }
+        };
+
+        let template_type = match matches.value_of("type").unwrap_or(extension.as_str()) {
+            "md" | "markdown" => docgen::RenderType::Markdown,
+            _ => docgen::RenderType::Html,
+        };
+
+        let options = render_options(matches)?;
+
+        let (rt, cx) = docgen::init_js();
+        let rendered = match docgen::render_recursive_string(
+            &rt,
+            cx,
+            &mut template,
+            template_type,
+            path,
+            None,
+            std::rc::Rc::new(None),
+            None,
+            Some(vars),
+            &options,
+        ) {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                error!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        print_warnings(&options.warnings.borrow());
+
+        io::stdout().write_all(rendered.html.as_bytes())?;
+        return Ok(());
+    }
+
+    let vars = collect_vars(&matches)?;
     let pattern = matches.value_of("input").unwrap();
     debug!("got pattern: {}", pattern);
     for entry in glob(pattern).expect("Failed to read input glob pattern") {
         match entry {
             Ok(path) => {
-                let res = docgen::render_recursive(&path, std::rc::Rc::new(None), None, None);
-                println!("{}", res);
+                match docgen::render_recursive(&path, std::rc::Rc::new(None), None, Some(vars.clone())) {
+                    Ok(res) => println!("{}", res),
+                    Err(err) => error!("{}", err.in_file(Some(&path))),
+                }
                 break;
             }
             Err(e) => error!("{:?}", e),
         }
     }
 
-    // let mut buffer = String::new();
-    // io::stdin().read_to_string(&mut buffer)?;
-
-    // println!(
-    //     "{}",
-    //     docgen::render(
-    //         &mut buffer,
-    //         serde_json::from_str(r###"{ "name": "Rich" }"###).unwrap()
-    //     )
-    // );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_vars_before_and_after_command() {
+        let help = crate::BuildHelp::new();
+        for args in [
+            vec!["docgen", "--var", "title=Example", "build"],
+            vec!["docgen", "build", "--var", "title=Example"],
+        ]
+        .iter()
+        {
+            let matches = crate::app(&help).get_matches_from_safe(args).unwrap();
+            let vars = crate::collect_vars(matches.subcommand_matches("build").unwrap()).unwrap();
+            assert_eq!(vars, serde_json::json!({ "title": "Example" }));
+        }
+    }
+
+    #[test]
+    fn test_render_loads_data() {
+        let base = std::env::temp_dir().join(format!("docgen_render_base_{}", std::process::id()));
+        std::fs::create_dir_all(base.join("_data")).unwrap();
+        std::fs::write(base.join("_data/site.json"), r###"{ "title": "Docgen" }"###).unwrap();
+
+        let help = crate::BuildHelp::new();
+        let args = vec!["docgen", "render", "--stdin", "--base", base.to_str().unwrap()];
+        let matches = crate::app(&help).get_matches_from_safe(args).unwrap();
+        let options = crate::render_options(matches.subcommand_matches("render").unwrap());
+        std::fs::remove_dir_all(&base).unwrap();
+
+        let mut template = r###"<p>{{data.site.title}}</p>"###.to_string();
+        let (rt, cx) = docgen::init_js();
+        let rendered = docgen::render_recursive_string(
+            &rt,
+            cx,
+            &mut template,
+            docgen::RenderType::Html,
+            None,
+            None,
+            std::rc::Rc::new(None),
+            None,
+            None,
+            &options.unwrap(),
+        );
+        match rendered {
+            Ok(rendered) => assert_eq!(rendered.html, "<html><head></head><body><p>Docgen</p></body></html>"),
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// File extensions which are rendered as pages. Anything else is copied into the output as-is.
+const PAGE_EXTENSIONS: [&str; 4] = ["html", "htm", "md", "markdown"];
+
+/// Settings for a whole-site build.
+pub struct BuildOptions {
+    /// The directory containing the site's pages, layouts and partials.
+    pub source: PathBuf,
+
+    /// The directory the rendered site is written to.
+    pub output: PathBuf,
+
+    /// Pattern used to place dated pages (posts) which don't set a `permalink`.
+    /// Supports `:year`, `:month`, `:day` and `:slug`.
+    pub permalink: String,
+
+    /// Directory of json, yaml and toml files exposed to every page as `data`. Skipped if it doesn't exist.
+    pub data: PathBuf,
+
+    /// Directory of components, which custom elements such as `<post-card>` render. Skipped if it doesn't exist.
+    pub components: PathBuf,
+
+    /// Globs (relative to the source directory) of files to build. Everything is built if empty.
+    pub include: Vec<String>,
+
+    /// Globs (relative to the source directory) of files to skip.
+    pub exclude: Vec<String>,
+
+    /// Layout used by pages which don't set one.
+    pub layout: Option<PathBuf>,
+
+    /// The url the site is published at, exposed as `site.base_url`.
+    pub base_url: Option<String>,
+
+    /// Extra variables merged into the `site` object.
+    pub variables: serde_json::Map<String, Value>,
+
+    /// Variables given on the command line, set on every page after `site`.
+    pub vars: serde_json::Map<String, Value>,
+
+    /// Fail pages on any error in a template expression, instead of reporting it as a warning.
+    pub strict: bool,
+}
+
+impl BuildOptions {
+    /// Resolve a site's configuration against the directory it was loaded from.
+    pub fn from_config(root: &Path, config: crate::config::Config) -> BuildOptions {
+        let source = root.join(&config.source);
+
+        BuildOptions {
+            data: source.join(&config.data),
+            components: source.join(&config.components),
+            output: root.join(&config.output),
+            permalink: config.permalink,
+            include: config.include,
+            exclude: config.exclude,
+            layout: config.layout.map(|layout| root.join(layout)),
+            base_url: config.base_url,
+            variables: config.site,
+            vars: serde_json::Map::new(),
+            strict: false,
+            source,
+        }
+    }
+}
+
+/// Compile a list of glob patterns.
+fn compile_patterns(patterns: &[String]) -> std::io::Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).map_err(|err| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid pattern {}: {}", pattern, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// The permalink pattern used when none is configured.
+pub const DEFAULT_PERMALINK: &str = "/:year/:month/:day/:slug";
+
+/// Summary of what happened during a build.
+pub struct BuildReport {
+    /// Pages which were rendered, as (source, destination) pairs.
+    pub rendered: Vec<(PathBuf, PathBuf)>,
+
+    /// Number of non-page files copied into the output.
+    pub copied: usize,
+
+    /// Errors for pages (or files) which could not be built.
+    pub failed: Vec<crate::DocgenError>,
+
+    /// Errors which pages were rendered around, when not building in strict mode.
+    pub warnings: Vec<crate::DocgenError>,
+}
+
+/// Whether a path points to a page that should be rendered (as opposed to a static file).
+pub fn is_page(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => PAGE_EXTENSIONS.contains(&ext),
+        None => false,
+    }
+}
+
+/// Files and directories starting with "_" (layouts, partials, ...) or "." are never part of the output.
+pub fn is_ignored(name: &str) -> bool {
+    name.starts_with('_') || name.starts_with('.')
+}
+
+/// Get the path a source file is written to, relative to the output directory.
+/// Markdown pages become html pages, everything else keeps its name.
+pub fn output_path(relative: &Path) -> PathBuf {
+    match relative.extension().and_then(|ext| ext.to_str()) {
+        Some("md") | Some("markdown") => relative.with_extension("html"),
+        _ => relative.to_path_buf(),
+    }
+}
+
+/// Parse the start of a date like "2019-07-04", "2019-7-4" or "2019-07-04 8:00:00 -0400" into
+/// [year, month, day, hour, minute, second]. The time is optional, and a timezone is ignored.
+fn parse_date(date: &str) -> Option<[u32; 6]> {
+    let date_regex = Regex::new(r###"^(\d{4})-(\d{1,2})-(\d{1,2})(?:[ T](\d{1,2}):(\d{2})(?::(\d{2}))?)?"###).unwrap();
+    let captures = date_regex.captures(date)?;
+
+    let mut parts = [0; 6];
+    for (i, part) in parts.iter_mut().enumerate() {
+        if let Some(value) = captures.get(i + 1) {
+            *part = value.as_str().parse().ok()?;
+        }
+    }
+
+    Some(parts)
+}
+
+/// Get the parsed date of a page.
+/// A `date` in the front matter takes precedence over a jekyll-style `YYYY-MM-DD-` file name prefix.
+fn page_date_parts(relative: &Path, matter: Option<&Value>) -> Option<[u32; 6]> {
+    let date = matter
+        .and_then(|matter| matter.get("date"))
+        .and_then(|date| date.as_str())
+        .or_else(|| relative.file_stem().and_then(|stem| stem.to_str()))?;
+
+    parse_date(date)
+}
+
+/// Get the date of a page as zero-padded (year, month, day).
+pub fn page_date(relative: &Path, matter: Option<&Value>) -> Option<(String, String, String)> {
+    page_date_parts(relative, matter).map(|parts| {
+        (
+            format!("{:04}", parts[0]),
+            format!("{:02}", parts[1]),
+            format!("{:02}", parts[2]),
+        )
+    })
+}
+
+/// Get the slug of a page: its file name without the extension or a date prefix.
+pub fn page_slug(relative: &Path) -> String {
+    let date_prefix = Regex::new(r###"^\d{4}-\d{1,2}-\d{1,2}-"###).unwrap();
+    let stem = relative
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+
+    date_prefix.replace(stem, "").to_string()
+}
+
+/// Build the permalink of a dated page from a pattern like "/:year/:month/:day/:slug".
+/// Pages without a date have no default permalink, and are placed by their source path instead.
+pub fn default_permalink(pattern: &str, relative: &Path, matter: Option<&Value>) -> Option<String> {
+    let (year, month, day) = page_date(relative, matter)?;
+
+    Some(
+        pattern
+            .replace(":year", &year)
+            .replace(":month", &month)
+            .replace(":day", &day)
+            .replace(":slug", &page_slug(relative)),
+    )
+}
+
+/// Get the path a permalink is written to, relative to the output directory.
+/// Permalinks without a file extension (like "/2018/02/12/hello/") are written as an index.html in that directory.
+pub fn permalink_path(permalink: &str) -> PathBuf {
+    // only keep normal components, so that a permalink can't escape the output directory.
+    let mut path: PathBuf = Path::new(permalink)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+
+    if permalink.ends_with('/') || path.extension().is_none() {
+        path.push("index.html");
+    }
+
+    path
+}
+
+/// Recursively collect every file beneath `dir` which is not ignored.
+fn collect_files(dir: &Path, skip: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>()?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let ignored = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => is_ignored(name),
+            None => true,
+        };
+
+        if ignored {
+            trace!("skipping {}", path.display());
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            // don't recurse into the output directory if it lives inside of the source.
+            if path.canonicalize()? == skip {
+                continue;
+            }
+
+            collect_files(&path, skip, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Metadata about a page, collected before any page is rendered.
+pub struct PageInfo {
+    /// The page's source file.
+    pub path: PathBuf,
+
+    /// The page's source file, relative to the source directory.
+    pub relative: PathBuf,
+
+    /// The page's front matter, if it has any.
+    pub matter: Option<Value>,
+
+    /// The url the page is published at, from its permalink or its place in the source tree.
+    pub url: String,
+}
+
+impl PageInfo {
+    /// Read a page's front matter and work out where it will be published.
+    pub fn load(path: &Path, relative: &Path, permalink_pattern: &str) -> std::io::Result<PageInfo> {
+        let contents = std::fs::read_to_string(path)?;
+        let matter = crate::frontmatter::parse_to_json(&contents);
+
+        // an explicit permalink wins, then the permalink pattern for dated pages, then the source tree.
+        let url = matter
+            .as_ref()
+            .and_then(|matter| matter.get("permalink"))
+            .and_then(|permalink| permalink.as_str())
+            .map(|permalink| permalink.to_string())
+            .or_else(|| default_permalink(permalink_pattern, relative, matter.as_ref()))
+            .unwrap_or_else(|| {
+                let parts = output_path(relative)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<String>>();
+                format!("/{}", parts.join("/"))
+            });
+
+        Ok(PageInfo {
+            path: path.to_path_buf(),
+            relative: relative.to_path_buf(),
+            matter,
+            url,
+        })
+    }
+
+    /// Whether the page is a post, meaning it has a date.
+    pub fn is_post(&self) -> bool {
+        page_date(&self.relative, self.matter.as_ref()).is_some()
+    }
+
+    /// The page as it is exposed to templates: its front matter, plus `url`, `path` and `date`.
+    pub fn to_json(&self) -> Value {
+        let mut page = match &self.matter {
+            Some(Value::Object(map)) => map.clone(),
+            _ => serde_json::Map::new(),
+        };
+
+        page.insert("url".to_string(), Value::String(self.url.clone()));
+        page.insert(
+            "path".to_string(),
+            Value::String(self.relative.to_string_lossy().to_string()),
+        );
+
+        if !page.contains_key("date") {
+            if let Some((year, month, day)) = page_date(&self.relative, None) {
+                page.insert(
+                    "date".to_string(),
+                    Value::String(format!("{}-{}-{}", year, month, day)),
+                );
+            }
+        }
+
+        Value::Object(page)
+    }
+}
+
+/// Build the `site` object exposed to every page.
+/// `site.pages` holds every page in source order, `site.posts` holds dated pages, newest first.
+pub fn site_json<'a, I: IntoIterator<Item = &'a PageInfo>>(pages: I) -> Value {
+    let pages = pages.into_iter().collect::<Vec<&PageInfo>>();
+
+    let mut posts = pages
+        .iter()
+        .filter(|page| page.is_post())
+        .collect::<Vec<&&PageInfo>>();
+
+    // dates are compared once parsed, since they aren't always zero-padded. Posts on the same date keep their
+    // source order.
+    posts.sort_by_key(|page| std::cmp::Reverse(page_date_parts(&page.relative, page.matter.as_ref())));
+
+    serde_json::json!({
+        "pages": pages.iter().map(|page| page.to_json()).collect::<Vec<Value>>(),
+        "posts": posts.iter().map(|page| page.to_json()).collect::<Vec<Value>>(),
+    })
+}
+
+/// Render every page beneath the source directory into the output directory, mirroring the source tree.
+pub fn build_site(options: &BuildOptions) -> std::io::Result<BuildReport> {
+    std::fs::create_dir_all(&options.output)?;
+    let output_root = options.output.canonicalize()?;
+
+    let mut files: Vec<PathBuf> = vec![];
+    collect_files(&options.source, &output_root, &mut files)?;
+
+    let include = compile_patterns(&options.include)?;
+    let exclude = compile_patterns(&options.exclude)?;
+
+    let mut report = BuildReport {
+        rendered: vec![],
+        copied: 0,
+        failed: vec![],
+        warnings: vec![],
+    };
+
+    // 1. copy static files, and collect metadata for every page.
+    let mut pages: Vec<PageInfo> = vec![];
+    for path in files {
+        let relative = path.strip_prefix(&options.source).unwrap().to_path_buf();
+
+        // the configuration file isn't part of the site when the source is also the root.
+        let is_config = relative
+            .to_str()
+            .map_or(false, |name| crate::config::CONFIG_FILES.contains(&name));
+        let included = include.is_empty() || include.iter().any(|pattern| pattern.matches_path(&relative));
+        if is_config || !included || exclude.iter().any(|pattern| pattern.matches_path(&relative)) {
+            trace!("skipping {}", path.display());
+            continue;
+        }
+
+        if is_page(&path) {
+            match PageInfo::load(&path, &relative, &options.permalink) {
+                Ok(page) => pages.push(page),
+                Err(err) => {
+                    let err = crate::DocgenError::io(&path, err);
+                    error!("{}", err);
+                    report.failed.push(err);
+                }
+            }
+            continue;
+        }
+
+        let destination = options.output.join(output_path(&relative));
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        match std::fs::copy(&path, &destination) {
+            Ok(_) => report.copied += 1,
+            Err(err) => report.failed.push(crate::DocgenError::io(&destination, err)),
+        }
+    }
+
+    // 2. render each page, with the whole site and its data files available to it.
+    let mut site = options.variables.clone();
+    if let Some(base_url) = &options.base_url {
+        site.insert("base_url".to_string(), Value::String(base_url.clone()));
+    }
+
+    let mut render_options = crate::RenderOptions::default();
+    render_options.layout = options.layout.clone();
+    render_options.root = Some(options.source.clone());
+    render_options.strict = options.strict;
+
+    if options.data.is_dir() {
+        let data = crate::data::load_data_dir(&options.data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        // the data is serialized once, rather than converted again for every page.
+        render_options.data = Some(data.to_json().to_string());
+    }
+
+    if options.components.is_dir() {
+        render_options.components = crate::components::load_components_dir(&options.components)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    }
+
+    let (rt, cx) = crate::init_js();
+
+    let render = |page: &PageInfo, collections: &Value| {
+        let mut site = site.clone();
+        if let Value::Object(collections) = collections {
+            site.extend(collections.clone());
+        }
+
+        let mut set_vars = serde_json::json!({ "site": Value::Object(site) });
+        crate::vars::merge_vars(&mut set_vars, Value::Object(options.vars.clone()));
+
+        debug!("building {}", page.path.display());
+        let rendered = crate::render_recursive_path(
+            &rt,
+            cx,
+            &page.path,
+            None,
+            Rc::new(None),
+            None,
+            Some(set_vars),
+            &render_options,
+        );
+
+        let warnings: Vec<crate::DocgenError> = render_options.warnings.borrow_mut().drain(..).collect();
+        (rendered, warnings)
+    };
+
+    // the listing collected up front includes every page, at the url from its front matter.
+    let collections = site_json(&pages);
+    let results: Vec<_> = pages
+        .into_iter()
+        .map(|page| {
+            let (rendered, warnings) = render(&page, &collections);
+            (page, rendered, warnings)
+        })
+        .collect();
+
+    // 3. work out which pages are published, and where. Pages are only published once every page has been
+    // rendered, so that files which turned out to be another page's layout or partial (such as a layout.html next
+    // to the pages using it) aren't published as pages themselves.
+    let mut published = vec![];
+    // the source file written to each destination, so that pages with the same permalink don't overwrite each other.
+    let mut destinations: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for (mut page, rendered, warnings) in results {
+        let path = page.path.clone();
+        if render_options.is_included(&path) {
+            info!("{} is a layout or partial of another page, so it isn't built", path.display());
+            continue;
+        }
+
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                report
+                    .warnings
+                    .extend(warnings.into_iter().map(|warning| warning.in_file(Some(&path))));
+                let err = err.in_file(Some(&path));
+                error!("{}", err);
+                report.failed.push(err);
+                continue;
+            }
+        };
+
+        // a permalink set by a static script overrides the one from the front matter.
+        if let Some(permalink) = &rendered.permalink {
+            page.url = permalink.clone();
+        }
+        let destination = options.output.join(permalink_path(&page.url));
+
+        if let Some(other) = destinations.get(&destination) {
+            let err = crate::DocgenError::template(format!(
+                "{} is already built from {}",
+                destination.display(),
+                other.display()
+            ))
+            .in_file(Some(&path));
+            error!("{}", err);
+            report.failed.push(err);
+            continue;
+        }
+        destinations.insert(destination.clone(), path);
+
+        published.push((page, destination, Ok(rendered), warnings));
+    }
+
+    // 4. `site.pages` and `site.posts` only list the published pages, at their final urls. When that differs from
+    // the listing the pages were rendered with, they're rendered again with the right one.
+    let published_collections = site_json(published.iter().map(|(page, _, _, _)| page));
+    if published_collections != collections {
+        debug!("the published pages differ from the pages found, so they're rendered again");
+        for (page, _, rendered, warnings) in published.iter_mut() {
+            let (rerendered, rewarnings) = render(page, &published_collections);
+            *rendered = rerendered;
+            *warnings = rewarnings;
+        }
+    }
+
+    // 5. write the published pages.
+    for (page, destination, rendered, warnings) in published {
+        let path = page.path;
+        report
+            .warnings
+            .extend(warnings.into_iter().map(|warning| warning.in_file(Some(&path))));
+
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                let err = err.in_file(Some(&path));
+                error!("{}", err);
+                report.failed.push(err);
+                continue;
+            }
+        };
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        match std::fs::write(&destination, rendered.html) {
+            Ok(_) => {
+                info!("{} -> {}", path.display(), destination.display());
+                report.rendered.push((path, destination));
+            }
+            Err(err) => {
+                let err = crate::DocgenError::io(&destination, err).in_file(Some(&path));
+                error!("{}", err);
+                report.failed.push(err);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(
+            crate::site::output_path(Path::new("blog/2018-02-12-visualizing-algorithms.md")),
+            PathBuf::from("blog/2018-02-12-visualizing-algorithms.html")
+        );
+        assert_eq!(
+            crate::site::output_path(Path::new("about/index.html")),
+            PathBuf::from("about/index.html")
+        );
+        assert_eq!(
+            crate::site::output_path(Path::new("css/style.css")),
+            PathBuf::from("css/style.css")
+        );
+    }
+
+    #[test]
+    fn test_default_permalink() {
+        let pattern = crate::site::DEFAULT_PERMALINK;
+
+        assert_eq!(
+            crate::site::default_permalink(pattern, Path::new("2018-02-12-visualizing-algorithms.md"), None),
+            Some("/2018/02/12/visualizing-algorithms".to_string())
+        );
+
+        let matter = serde_json::json!({ "date": "2019-07-04 8:00:00 -0400" });
+        assert_eq!(
+            crate::site::default_permalink(pattern, Path::new("posts/fireworks.md"), Some(&matter)),
+            Some("/2019/07/04/fireworks".to_string())
+        );
+
+        assert_eq!(
+            crate::site::default_permalink(pattern, Path::new("2018-2-1-unpadded.md"), None),
+            Some("/2018/02/01/unpadded".to_string())
+        );
+
+        assert_eq!(
+            crate::site::default_permalink(pattern, Path::new("about.md"), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_site_posts_sorted_by_date() {
+        let page = |relative: &str, matter: serde_json::Value| crate::site::PageInfo {
+            path: PathBuf::from(relative),
+            relative: PathBuf::from(relative),
+            matter: Some(matter),
+            url: format!("/{}", relative),
+        };
+
+        let pages = vec![
+            page("about.html", serde_json::json!({ "title": "About" })),
+            page("2018-02-12-older.md", serde_json::json!({ "title": "Older" })),
+            page("newer.md", serde_json::json!({ "title": "Newer", "date": "2019-01-01" })),
+            page("september.md", serde_json::json!({ "title": "September", "date": "2018-9-30" })),
+            page("2018-10-1-october.md", serde_json::json!({ "title": "October" })),
+        ];
+
+        let site = crate::site::site_json(&pages);
+        assert_eq!(site["pages"].as_array().unwrap().len(), 5);
+        assert_eq!(site["pages"][0]["path"], "about.html");
+
+        let posts = site["posts"].as_array().unwrap();
+        let titles: Vec<&str> = posts.iter().map(|post| post["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, vec!["Newer", "October", "September", "Older"]);
+        assert_eq!(posts[1]["date"], "2018-10-01");
+        assert_eq!(posts[3]["date"], "2018-02-12");
+    }
+
+    #[test]
+    fn test_site_lists_published_pages() {
+        let dir = crate::testing::TempDir::new("site_listing");
+        let index = r###"<layout src="./layout.html"></layout><p x-for="page of site.pages">{{page.url}}</p>"###;
+        dir.write("index.html", index);
+        dir.write("layout.html", r###"<main><slot name="content"></slot></main>"###);
+        dir.write("moved.html", r###"<script static>permalink = "/moved/";</script><p>Moved</p>"###);
+
+        let options = crate::site::BuildOptions::from_config(dir.path(), crate::config::Config::default());
+        let report = crate::site::build_site(&options).unwrap();
+        assert!(report.failed.is_empty());
+
+        // the layout isn't published, so it isn't listed, and the moved page is listed at its final url.
+        let built = std::fs::read_to_string(options.output.join("index.html")).unwrap();
+        assert!(built.contains("<main><p>/index.html</p><p>/moved/</p></main>"));
+    }
+
+    #[test]
+    fn test_permalink_path() {
+        assert_eq!(
+            crate::site::permalink_path("/2018/02/12/visualizing-algorithms"),
+            PathBuf::from("2018/02/12/visualizing-algorithms/index.html")
+        );
+        assert_eq!(
+            crate::site::permalink_path("/about/"),
+            PathBuf::from("about/index.html")
+        );
+        assert_eq!(
+            crate::site::permalink_path("/feed.xml"),
+            PathBuf::from("feed.xml")
+        );
+        assert_eq!(
+            crate::site::permalink_path("/../../etc/passwd.html"),
+            PathBuf::from("etc/passwd.html")
+        );
+    }
+
+    #[test]
+    fn test_ignored() {
+        assert!(crate::site::is_ignored("_layouts"));
+        assert!(crate::site::is_ignored("_footer.html"));
+        assert!(crate::site::is_ignored(".git"));
+        assert!(!crate::site::is_ignored("index.html"));
+    }
+
+    #[test]
+    fn test_is_page() {
+        assert!(crate::site::is_page(Path::new("index.html")));
+        assert!(crate::site::is_page(Path::new("posts/hello.md")));
+        assert!(!crate::site::is_page(Path::new("style.css")));
+        assert!(!crate::site::is_page(Path::new("README")));
+    }
+
+    #[test]
+    fn test_config_layout_from_root() {
+        let mut config = crate::config::Config::default();
+        config.source = PathBuf::from("src");
+        config.layout = Some("_layouts/base.html".to_string());
+
+        let options = crate::site::BuildOptions::from_config(Path::new("site"), config);
+        assert_eq!(options.layout, Some(PathBuf::from("site/_layouts/base.html")));
+    }
+
+    #[test]
+    fn test_included_pages_not_built() {
+        let dir = crate::testing::TempDir::new("site_included");
+        dir.write(
+            "index.html",
+            r###"<layout src="./layout.html"><b slot="title">Home</b></layout><slot src="./footer.html"></slot>"###,
+        );
+        dir.write("layout.html", r###"<h1><slot name="title"></slot></h1>"###);
+        dir.write("footer.html", r###"<footer>Footer</footer>"###);
+        dir.write("about.html", r###"<p>About</p>"###);
+
+        let options = crate::site::BuildOptions::from_config(dir.path(), crate::config::Config::default());
+        let report = crate::site::build_site(&options).unwrap();
+
+        assert!(report.failed.is_empty());
+        let mut built: Vec<PathBuf> = report
+            .rendered
+            .iter()
+            .map(|(source, _)| PathBuf::from(source.file_name().unwrap()))
+            .collect();
+        built.sort();
+        assert_eq!(built, vec![PathBuf::from("about.html"), PathBuf::from("index.html")]);
+    }
+
+    #[test]
+    fn test_permalink_collision() {
+        let dir = crate::testing::TempDir::new("site_collision");
+        let first = dir.write("a.html", "---\npermalink: /same/\n---\n<p>A</p>");
+        let second = dir.write("b.html", "---\npermalink: /same/\n---\n<p>B</p>");
+
+        let options = crate::site::BuildOptions::from_config(dir.path(), crate::config::Config::default());
+        let report = crate::site::build_site(&options).unwrap();
+
+        assert_eq!(report.rendered.len(), 1);
+        assert_eq!(report.rendered[0].0, first);
+        assert_eq!(report.failed.len(), 1);
+
+        let err = &report.failed[0];
+        assert_eq!(err.file, Some(second));
+        assert!(format!("{}", err).contains(&format!("{}", first.display())));
+        assert!(std::fs::read_to_string(&report.rendered[0].1).unwrap().contains("<p>A</p>"));
+    }
+}
@@ -0,0 +1,423 @@
+use html5ever::interface::{Attribute, QualName};
+use html5ever::rcdom::{Handle, Node, RcDom};
+use html5ever::tendril::StrTendril;
+use html5ever::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
+use html5ever::ExpandedName;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+
+/// Where an expression was written in a template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    /// The template's file, if it was read from one.
+    pub file: Option<PathBuf>,
+
+    /// The line in the file, starting at 1.
+    pub line: usize,
+
+    /// The column in that line, starting at 1, if the expression could be found in it.
+    pub column: Option<usize>,
+
+    /// The contents of the line, for code frames.
+    pub text: String,
+}
+
+/// Where the nodes of a parsed template came from, so errors can point at the template.
+#[derive(Default)]
+pub struct SourceMap {
+    /// The template, split into lines.
+    lines: Vec<String>,
+
+    /// The line of the file the template starts on. Front matter pushes it down.
+    first_line: usize,
+
+    /// The line (in the template) each node was parsed on, keyed by node.
+    /// A weak reference to each node is kept with its line, so its address can't be reused by another node.
+    nodes: HashMap<*const Node, (Weak<Node>, usize)>,
+
+    /// Whether the nodes weren't parsed from the template itself (markdown is converted to html first), so
+    /// expressions are looked for in the order they're rendered instead, starting from `cursor`.
+    in_order: bool,
+
+    /// Where to look for the next expression, as (line index, byte offset), for templates searched in order.
+    cursor: Cell<(usize, usize)>,
+}
+
+impl SourceMap {
+    pub fn new(template: &str, first_line: usize) -> SourceMap {
+        SourceMap {
+            lines: template.lines().map(|line| line.to_string()).collect(),
+            first_line,
+            nodes: HashMap::new(),
+            in_order: false,
+            cursor: Cell::new((0, 0)),
+        }
+    }
+
+    /// A map for a template which was converted before it was parsed, like markdown. Expressions are copied into
+    /// the html as they were written, and rendered in the same order, so each one is looked for from where the
+    /// previous one was found.
+    pub fn in_order(template: &str, first_line: usize) -> SourceMap {
+        SourceMap {
+            in_order: true,
+            ..SourceMap::new(template, first_line)
+        }
+    }
+
+    /// The line a node was parsed on, or the line of its nearest parsed ancestor for generated nodes.
+    fn line_of(&self, node: &Rc<Node>) -> Option<usize> {
+        if let Some((_, line)) = self.nodes.get(&(&**node as *const Node)) {
+            return Some(*line);
+        }
+
+        let parent = node.parent.take();
+        node.parent.set(parent.clone());
+        parent
+            .and_then(|parent| parent.upgrade())
+            .and_then(|parent| self.line_of(&parent))
+    }
+
+    /// Find the first occurrence of `needle` at or after the line `node` was parsed on.
+    /// Tags spanning several lines are reported on their last line, so earlier lines are checked after that.
+    /// `skip` moves the reported column past a prefix of the needle, such as an attribute's name.
+    fn find(&self, node: &Rc<Node>, needle: &str, skip: usize) -> Option<SourceLocation> {
+        if self.in_order {
+            return self.find_next(needle, skip);
+        }
+
+        self.find_from(self.line_of(node)?, needle, skip)
+    }
+
+    /// Find the next occurrence of `needle` after the start of the last one found, wrapping around to the start of
+    /// the template (for the next iteration of a loop). Expressions which aren't written as-is can't be found.
+    fn find_next(&self, needle: &str, skip: usize) -> Option<SourceLocation> {
+        let needle = needle.lines().next().unwrap_or("");
+        let (line, from) = self.cursor.get();
+        let after = (line..self.lines.len()).map(|index| (index, if index == line { from } else { 0 }));
+        let before = (0..=line).map(|index| (index, 0));
+
+        for (index, from) in after.chain(before) {
+            let text = match self.lines.get(index) {
+                Some(text) => text,
+                None => continue,
+            };
+
+            if let Some(offset) = text.get(from..).and_then(|rest| rest.find(needle)).map(|found| from + found) {
+                // the next expression may be on the same line, even the same expression again.
+                let next = text[offset..].chars().next().map_or(1, char::len_utf8);
+                self.cursor.set((index, offset + next));
+
+                return Some(SourceLocation {
+                    file: None,
+                    line: self.first_line + index,
+                    column: Some(text[..offset].chars().count() + skip + 1),
+                    text: text.clone(),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Find the first occurrence of `needle` at or after the line `start`, then before it.
+    fn find_from(&self, start: usize, needle: &str, skip: usize) -> Option<SourceLocation> {
+        let start = start.min(self.lines.len()).max(1);
+        let needle = needle.lines().next().unwrap_or("");
+        let after = (start - 1..self.lines.len()).chain((0..start - 1).rev());
+
+        for index in after {
+            let text = &self.lines[index];
+            if let Some(offset) = text.find(needle) {
+                return Some(SourceLocation {
+                    file: None,
+                    line: self.first_line + index,
+                    column: Some(text[..offset].chars().count() + skip + 1),
+                    text: text.clone(),
+                });
+            }
+        }
+
+        // the expression isn't written as-is (it contained entities, for example), so point at the node.
+        Some(SourceLocation {
+            file: None,
+            line: self.first_line + start - 1,
+            column: None,
+            text: self.lines.get(start - 1).cloned().unwrap_or_default(),
+        })
+    }
+
+    /// Locate an expression in a text node, such as `{{ title }}`.
+    pub fn locate_text(&self, node: &Rc<Node>, expression: &str) -> Option<SourceLocation> {
+        self.find(node, expression, 0)
+    }
+
+    /// Locate the expression at `offset` in the contents of a text node, so that an expression repeated on one
+    /// line is told apart from the first one.
+    pub fn locate_in_text(&self, node: &Rc<Node>, text: &str, offset: usize) -> Option<SourceLocation> {
+        // find the whole line of the text the expression is on, starting from the line it should be on.
+        let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = text[offset..].find('\n').map_or(text.len(), |index| offset + index);
+        if self.in_order {
+            // only the expression itself, since markdown may have changed the text around it (like quotes).
+            let end = text[offset..line_end].find("}}").map_or(line_end, |index| offset + index + 2);
+            return self.find_next(&text[offset..end], 0);
+        }
+
+        let start = self.line_of(node)? + text[..line_start].matches('\n').count();
+
+        let mut location = self.find_from(start, &text[line_start..line_end], 0)?;
+        if let Some(column) = location.column {
+            location.column = Some(column + text[line_start..offset].chars().count());
+        }
+        Some(location)
+    }
+
+    /// Locate the value of an element's attribute, such as `:href="link.url"`.
+    pub fn locate_attribute(&self, node: &Rc<Node>, name: &str) -> Option<SourceLocation> {
+        self.find(node, &format!("{}=", name), name.len() + 2)
+    }
+
+    /// Give the nodes of a deep clone the lines of the nodes they were cloned from.
+    pub fn copy_lines(&mut self, from: &Rc<Node>, to: &Rc<Node>) {
+        if let Some((_, line)) = self.nodes.get(&(&**from as *const Node)).cloned() {
+            self.nodes.insert(&**to as *const Node, (Rc::downgrade(to), line));
+        }
+
+        let from_children = from.children.borrow();
+        let to_children = to.children.borrow();
+        for (from, to) in from_children.iter().zip(to_children.iter()) {
+            self.copy_lines(from, to);
+        }
+    }
+}
+
+/// Parse an html document, recording the line each node was parsed on.
+pub struct LineTrackingSink {
+    pub dom: RcDom,
+    line: u64,
+    nodes: HashMap<*const Node, (Weak<Node>, usize)>,
+}
+
+impl LineTrackingSink {
+    pub fn new() -> LineTrackingSink {
+        LineTrackingSink {
+            dom: RcDom::default(),
+            line: 1,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Add the recorded lines to a source map.
+    pub fn into_source_map(self, map: &mut SourceMap) -> RcDom {
+        map.nodes.extend(self.nodes);
+        self.dom
+    }
+
+    fn record(&mut self, node: &Handle) {
+        let line = self.line as usize;
+        self.nodes.entry(&**node as *const Node).or_insert_with(|| (Rc::downgrade(node), line));
+    }
+
+    /// Text is merged into the previous text node when appended, so record whichever node it ended up in.
+    fn record_last_child(&mut self, parent: &Handle) {
+        let last = parent.children.borrow().last().cloned();
+        if let Some(last) = last {
+            self.record(&last);
+        }
+    }
+}
+
+impl TreeSink for LineTrackingSink {
+    type Handle = Handle;
+    type Output = Self;
+
+    fn finish(self) -> Self {
+        self
+    }
+
+    fn parse_error(&mut self, msg: Cow<'static, str>) {
+        self.dom.parse_error(msg)
+    }
+
+    fn get_document(&mut self) -> Handle {
+        self.dom.get_document()
+    }
+
+    fn elem_name<'a>(&'a self, target: &'a Handle) -> ExpandedName<'a> {
+        self.dom.elem_name(target)
+    }
+
+    fn create_element(&mut self, name: QualName, attrs: Vec<Attribute>, flags: ElementFlags) -> Handle {
+        let node = self.dom.create_element(name, attrs, flags);
+        self.record(&node);
+        node
+    }
+
+    fn create_comment(&mut self, text: StrTendril) -> Handle {
+        let node = self.dom.create_comment(text);
+        self.record(&node);
+        node
+    }
+
+    fn create_pi(&mut self, target: StrTendril, data: StrTendril) -> Handle {
+        let node = self.dom.create_pi(target, data);
+        self.record(&node);
+        node
+    }
+
+    fn append(&mut self, parent: &Handle, child: NodeOrText<Handle>) {
+        self.dom.append(parent, child);
+        self.record_last_child(parent);
+    }
+
+    fn append_based_on_parent_node(&mut self, element: &Handle, prev_element: &Handle, child: NodeOrText<Handle>) {
+        self.dom.append_based_on_parent_node(element, prev_element, child)
+    }
+
+    fn append_doctype_to_document(&mut self, name: StrTendril, public_id: StrTendril, system_id: StrTendril) {
+        self.dom.append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn mark_script_already_started(&mut self, node: &Handle) {
+        self.dom.mark_script_already_started(node)
+    }
+
+    fn get_template_contents(&mut self, target: &Handle) -> Handle {
+        self.dom.get_template_contents(target)
+    }
+
+    fn same_node(&self, x: &Handle, y: &Handle) -> bool {
+        self.dom.same_node(x, y)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.dom.set_quirks_mode(mode)
+    }
+
+    fn append_before_sibling(&mut self, sibling: &Handle, new_node: NodeOrText<Handle>) {
+        self.dom.append_before_sibling(sibling, new_node)
+    }
+
+    fn add_attrs_if_missing(&mut self, target: &Handle, attrs: Vec<Attribute>) {
+        self.dom.add_attrs_if_missing(target, attrs)
+    }
+
+    fn remove_from_parent(&mut self, target: &Handle) {
+        self.dom.remove_from_parent(target)
+    }
+
+    fn reparent_children(&mut self, node: &Handle, new_parent: &Handle) {
+        self.dom.reparent_children(node, new_parent)
+    }
+
+    fn is_mathml_annotation_xml_integration_point(&self, handle: &Handle) -> bool {
+        self.dom.is_mathml_annotation_xml_integration_point(handle)
+    }
+
+    fn set_current_line(&mut self, line_number: u64) {
+        self.line = line_number;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{LineTrackingSink, SourceMap};
+    use html5ever::driver::ParseOpts;
+    use html5ever::rcdom::{Handle, NodeData};
+    use html5ever::tendril::TendrilSink;
+
+    fn find_element(node: &Handle, name: &str) -> Option<Handle> {
+        if let NodeData::Element { name: element_name, .. } = &node.data {
+            if element_name.local.to_string() == name {
+                return Some(node.clone());
+            }
+        }
+
+        for child in node.children.borrow().iter() {
+            if let Some(found) = find_element(child, name) {
+                return Some(found);
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn test_locate_expressions() {
+        let template = "<html>\n<body>\n  <p>\n    {{ title }}\n  </p>\n  <a :href=\"link.url\">x</a>\n</body>\n</html>";
+
+        let sink = html5ever::parse_document(LineTrackingSink::new(), ParseOpts::default())
+            .from_utf8()
+            .read_from(&mut template.as_bytes())
+            .unwrap();
+
+        // pretend the template followed 3 lines of front matter.
+        let mut map = SourceMap::new(template, 4);
+        let dom = sink.into_source_map(&mut map);
+
+        let paragraph = find_element(&dom.document, "p").unwrap();
+        let text = paragraph.children.borrow()[0].clone();
+        let location = map.locate_text(&text, "{{ title }}").unwrap();
+        assert_eq!(location.line, 7);
+        assert_eq!(location.column, Some(5));
+        assert_eq!(location.text, "    {{ title }}");
+
+        let link = find_element(&dom.document, "a").unwrap();
+        let location = map.locate_attribute(&link, ":href").unwrap();
+        assert_eq!(location.line, 9);
+        assert_eq!(location.column, Some(13));
+    }
+
+    #[test]
+    fn test_locate_repeated_expressions() {
+        let template = "<html>\n<body>\n  <p>{{ a }} and {{ a }}</p>\n</body>\n</html>";
+
+        let sink = html5ever::parse_document(LineTrackingSink::new(), ParseOpts::default())
+            .from_utf8()
+            .read_from(&mut template.as_bytes())
+            .unwrap();
+
+        let mut map = SourceMap::new(template, 1);
+        let dom = sink.into_source_map(&mut map);
+
+        let paragraph = find_element(&dom.document, "p").unwrap();
+        let text = paragraph.children.borrow()[0].clone();
+        let contents = "{{ a }} and {{ a }}";
+
+        let first = map.locate_in_text(&text, contents, 0).unwrap();
+        assert_eq!((first.line, first.column), (3, Some(6)));
+
+        let second = map.locate_in_text(&text, contents, 12).unwrap();
+        assert_eq!((second.line, second.column), (3, Some(18)));
+    }
+
+    #[test]
+    fn test_locate_in_order() {
+        // markdown isn't parsed as it was written, so expressions are found in the order they're rendered.
+        let template = "# {{ a }}\n\n{{ b }} and {{ b }}\n";
+        let map = SourceMap::in_order(template, 4);
+        let dom = html5ever::parse_document(html5ever::rcdom::RcDom::default(), ParseOpts::default())
+            .from_utf8()
+            .read_from(&mut "<p></p>".as_bytes())
+            .unwrap();
+        let node = &dom.document;
+
+        let heading = map.locate_text(node, "{{ a }}").unwrap();
+        assert_eq!((heading.line, heading.column), (4, Some(3)));
+
+        let first = map.locate_in_text(node, "{{ b }} and {{ b }}", 0).unwrap();
+        assert_eq!((first.line, first.column), (6, Some(1)));
+
+        let second = map.locate_in_text(node, "{{ b }} and {{ b }}", 12).unwrap();
+        assert_eq!((second.line, second.column), (6, Some(13)));
+
+        // the next iteration of a loop starts over.
+        let again = map.locate_text(node, "{{ a }}").unwrap();
+        assert_eq!((again.line, again.column), (4, Some(3)));
+
+        assert_eq!(map.locate_text(node, "{{ missing }}"), None);
+    }
+}
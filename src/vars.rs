@@ -0,0 +1,102 @@
+use serde_json::Value as JSONValue;
+use serde_yaml::Value as YAMLValue;
+use std::path::Path;
+
+/// Parse a `key=value` variable from the command line.
+/// The value is read as json when it parses (`true`, `3`, `[1, 2]`), and as a plain string otherwise.
+/// Dotted keys set nested values, so `site.base_url=/` becomes `{ "site": { "base_url": "/" } }`.
+pub fn parse_var(var: &str) -> Result<JSONValue, String> {
+    let index = match var.find('=') {
+        Some(index) => index,
+        None => return Err(format!("expected key=value, got \"{}\"", var)),
+    };
+
+    let (key, value) = (&var[..index], &var[index + 1..]);
+    if key.is_empty() || key.split('.').any(|part| part.is_empty()) {
+        return Err(format!("invalid variable name \"{}\"", key));
+    }
+
+    let mut value = serde_json::from_str(value).unwrap_or_else(|_| JSONValue::String(value.to_string()));
+    for part in key.rsplit('.') {
+        let mut object = serde_json::Map::new();
+        object.insert(part.to_string(), value);
+        value = JSONValue::Object(object);
+    }
+
+    Ok(value)
+}
+
+/// Load variables from a json or yaml file. The file must contain an object.
+pub fn load_vars_file(path: &Path) -> Result<JSONValue, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|err| format!("{}", err)),
+        _ => serde_yaml::from_str::<YAMLValue>(&contents)
+            .map_err(|err| format!("{}", err))
+            .and_then(|value| serde_json::to_value(value).map_err(|err| format!("{}", err))),
+    }
+    .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    match value {
+        JSONValue::Object(_) => Ok(value),
+        _ => Err(format!("{}: variables file must contain an object", path.display())),
+    }
+}
+
+/// Merge `source` into `target`. Objects are merged key by key, anything else is replaced.
+pub fn merge_vars(target: &mut JSONValue, source: JSONValue) {
+    match (target, source) {
+        (JSONValue::Object(target), JSONValue::Object(source)) => {
+            for (key, value) in source {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_vars(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, source) => *target = source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(crate::vars::parse_var("name=Rich").unwrap(), json!({ "name": "Rich" }));
+        assert_eq!(crate::vars::parse_var("draft=true").unwrap(), json!({ "draft": true }));
+        assert_eq!(crate::vars::parse_var("query=a=b").unwrap(), json!({ "query": "a=b" }));
+        assert_eq!(crate::vars::parse_var("empty=").unwrap(), json!({ "empty": "" }));
+        assert_eq!(
+            crate::vars::parse_var("site.base_url=https://staging.richinfante.com").unwrap(),
+            json!({ "site": { "base_url": "https://staging.richinfante.com" } })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_var() {
+        assert!(crate::vars::parse_var("name").is_err());
+        assert!(crate::vars::parse_var("=Rich").is_err());
+        assert!(crate::vars::parse_var("site..url=/").is_err());
+    }
+
+    #[test]
+    fn test_merge_vars() {
+        let mut vars = json!({ "site": { "title": "Docgen", "base_url": "/" }, "draft": false });
+        crate::vars::merge_vars(&mut vars, json!({ "site": { "base_url": "/staging/" } }));
+        crate::vars::merge_vars(&mut vars, json!({ "draft": true, "features": ["search"] }));
+
+        assert_eq!(
+            vars,
+            json!({
+                "site": { "title": "Docgen", "base_url": "/staging/" },
+                "draft": true,
+                "features": ["search"]
+            })
+        );
+    }
+}